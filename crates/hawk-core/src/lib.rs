@@ -4,6 +4,7 @@ use ion_rs::ReaderBuilder;
 use std::fs::File;
 
 pub mod source;
+pub mod template;
 
 pub fn read_some_ion_data() -> Result<()> {
     let ion_file = File::open("data.ion").unwrap();
@@ -0,0 +1,191 @@
+use anyhow::{anyhow, Result};
+use ion_rs::element::Value;
+use std::{borrow::Cow, cmp::Ordering, iter::Peekable, str::Chars, str::FromStr};
+use unicode_normalization::UnicodeNormalization;
+
+use super::ValueComparison;
+
+/// The Unicode normalization applied to text before it is compared.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Normalization {
+    #[default]
+    None,
+    /// Canonical composition: `"e\u{301}"` equals `"é"`.
+    Nfc,
+    /// Compatibility composition, which also folds variants such as `"ﬁ"`
+    /// into `"fi"` and full-width `"Ａ"` into `"A"`.
+    Nfkc,
+}
+
+/// How strings and symbols are compared. The default, binary collation
+/// compares their code points exactly; the others are named `nocase`, `nfc`,
+/// `nfkc` and `natural` and combine with `+`, as in `nfkc+nocase`.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Collation {
+    /// Compare the lowercased text.
+    pub case_insensitive: bool,
+    pub normalization: Normalization,
+    /// Compare runs of digits by their numeric value, so `"file9"` sorts
+    /// before `"file10"`.
+    pub natural: bool,
+}
+
+impl FromStr for Collation {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let mut collation = Collation::default();
+        for name in s.split('+') {
+            match name {
+                "binary" => {}
+                "nocase" => collation.case_insensitive = true,
+                "nfc" => collation.normalization = Normalization::Nfc,
+                "nfkc" => collation.normalization = Normalization::Nfkc,
+                "natural" => collation.natural = true,
+                _ => {
+                    return Err(anyhow!(
+                    "unknown collation \"{name}\" (expected binary, nocase, nfc, nfkc or natural)"
+                ))
+                }
+            }
+        }
+        Ok(collation)
+    }
+}
+
+/// Consumes a run of ASCII digits and returns it without leading zeros,
+/// along with how many zeros there were.
+fn digit_run(chars: &mut Peekable<Chars>) -> (String, usize) {
+    let mut digits = String::new();
+    let mut zeros = 0;
+    while let Some(c) = chars.next_if(char::is_ascii_digit) {
+        if c == '0' && digits.is_empty() {
+            zeros += 1;
+        } else {
+            digits.push(c);
+        }
+    }
+    (digits, zeros)
+}
+
+/// Orders text with runs of digits compared by value. Runs of equal value
+/// are told apart by their leading zeros only if nothing else differs, so
+/// `"a01"` and `"a1"` are close but not equal.
+fn natural_cmp(lhs: &str, rhs: &str) -> Ordering {
+    let (mut lhs, mut rhs) = (lhs.chars().peekable(), rhs.chars().peekable());
+    let mut zeros = Ordering::Equal;
+    loop {
+        match (lhs.peek(), rhs.peek()) {
+            (None, None) => return zeros,
+            (None, Some(_)) => return Ordering::Less,
+            (Some(_), None) => return Ordering::Greater,
+            (Some(l), Some(r)) if l.is_ascii_digit() && r.is_ascii_digit() => {
+                let ((l, l_zeros), (r, r_zeros)) = (digit_run(&mut lhs), digit_run(&mut rhs));
+                match l.len().cmp(&r.len()).then_with(|| l.cmp(&r)) {
+                    Ordering::Equal => zeros = zeros.then(r_zeros.cmp(&l_zeros)),
+                    order => return order,
+                }
+            }
+            (Some(l), Some(r)) => match l.cmp(r) {
+                Ordering::Equal => {
+                    lhs.next();
+                    rhs.next();
+                }
+                order => return order,
+            },
+        }
+    }
+}
+
+fn collated_text(value: &Value) -> Option<&str> {
+    match value {
+        Value::String(v) => Some(v.text()),
+        Value::Symbol(v) => v.text(),
+        _ => None,
+    }
+}
+
+impl Collation {
+    pub fn is_binary(&self) -> bool {
+        *self == Collation::default()
+    }
+
+    /// The text as it is compared: normalized, then lowercased. Two texts
+    /// are equal under the collation exactly when their keys are.
+    pub fn key<'a>(&self, text: &'a str) -> Cow<'a, str> {
+        let text: Cow<str> = match self.normalization {
+            Normalization::None => Cow::Borrowed(text),
+            Normalization::Nfc => Cow::Owned(text.nfc().collect()),
+            Normalization::Nfkc => Cow::Owned(text.nfkc().collect()),
+        };
+        match self.case_insensitive {
+            true => Cow::Owned(text.to_lowercase()),
+            false => text,
+        }
+    }
+
+    pub fn compare_text(&self, lhs: &str, rhs: &str) -> Ordering {
+        let (lhs, rhs) = (self.key(lhs), self.key(rhs));
+        match self.natural {
+            true => natural_cmp(&lhs, &rhs),
+            false => lhs.cmp(&rhs),
+        }
+    }
+
+    /// Orders two values as [`ValueComparison::compare`] does, except that
+    /// two strings, or two symbols, are ordered by this collation.
+    pub fn compare(&self, lhs: &Value, rhs: &Value) -> Option<Ordering> {
+        if !self.is_binary() && lhs.ion_type() == rhs.ion_type() {
+            if let (Some(l), Some(r)) = (collated_text(lhs), collated_text(rhs)) {
+                return Some(self.compare_text(l, r));
+            }
+        }
+        ValueComparison::compare(lhs, rhs)
+    }
+
+    pub fn equal(&self, lhs: &Value, rhs: &Value) -> bool {
+        match (collated_text(lhs), collated_text(rhs)) {
+            (Some(_), Some(_)) if !self.is_binary() => {
+                self.compare(lhs, rhs) == Some(Ordering::Equal)
+            }
+            _ => ValueComparison::equal(lhs, rhs),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_collation() {
+        let collation = |s: &str| s.parse::<Collation>().unwrap();
+        let cmp = |name: &str, lhs: &str, rhs: &str| collation(name).compare_text(lhs, rhs);
+
+        assert!(collation("binary").is_binary());
+        assert!("nocase+fold".parse::<Collation>().is_err());
+
+        assert_eq!(cmp("binary", "abc", "ABC"), Ordering::Greater);
+        assert_eq!(cmp("nocase", "abc", "ABC"), Ordering::Equal);
+        assert_eq!(cmp("nocase", "Straße", "STRASSE"), Ordering::Greater);
+        assert_eq!(cmp("binary", "e\u{301}", "é"), Ordering::Less);
+        assert_eq!(cmp("nfc", "e\u{301}", "é"), Ordering::Equal);
+        assert_eq!(cmp("nfc", "ﬁle", "file"), Ordering::Greater);
+        assert_eq!(cmp("nfkc", "ﬁle", "file"), Ordering::Equal);
+        assert_eq!(cmp("nfkc+nocase", "ＡＢＣ", "abc"), Ordering::Equal);
+
+        assert_eq!(cmp("binary", "file9", "file10"), Ordering::Greater);
+        assert_eq!(cmp("natural", "file9", "file10"), Ordering::Less);
+        assert_eq!(cmp("natural", "file10", "file10b"), Ordering::Less);
+        assert_eq!(cmp("natural", "v1.10", "v1.9"), Ordering::Greater);
+        assert_eq!(cmp("natural", "a01", "a1"), Ordering::Less);
+        assert_eq!(cmp("natural", "a01b", "a1a"), Ordering::Greater);
+        assert_eq!(cmp("natural", "x007", "x007"), Ordering::Equal);
+        assert_eq!(cmp("natural+nocase", "File9", "file10"), Ordering::Less);
+
+        let nocase = collation("nocase");
+        assert!(nocase.equal(&Value::from("Open"), &Value::from("OPEN")));
+        assert!(!nocase.equal(&Value::from("1"), &Value::from(1)));
+        assert!(nocase.equal(&Value::from(1), &Value::from(1.0)));
+    }
+}
@@ -1,22 +1,53 @@
 use anyhow::Result;
-use std::io::Read;
+use std::{cell::RefCell, io::Read, rc::Rc};
 
-use csv::StringRecord;
+use csv::{ReaderBuilder, StringRecord};
 use ion_rs::element::Element;
 
+/// Keeps a copy of the bytes read through it, so the text of a record can be
+/// recovered after the csv reader has split it into fields.
+struct Recorder<R: Read> {
+    inner: R,
+    bytes: Rc<RefCell<Vec<u8>>>,
+}
+
+impl<R: Read> Read for Recorder<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let read = self.inner.read(buf)?;
+        self.bytes.borrow_mut().extend_from_slice(&buf[..read]);
+        Ok(read)
+    }
+}
+
 pub struct CsvIonIterator<R: Read> {
-    reader: csv::Reader<R>,
+    reader: csv::Reader<Recorder<R>>,
     headers: Option<StringRecord>,
+    /// The input read but not yet returned as a record, starting at byte
+    /// `offset` of the input.
+    bytes: Rc<RefCell<Vec<u8>>>,
+    offset: u64,
+    line: String,
 }
 
 impl<R: Read> CsvIonIterator<R> {
-    pub fn new(mut reader: csv::Reader<R>) -> Result<Self> {
+    pub fn new(builder: &ReaderBuilder, input: R) -> Result<Self> {
+        let bytes = Rc::new(RefCell::new(Vec::new()));
+        let mut reader = builder.from_reader(Recorder {
+            inner: input,
+            bytes: bytes.clone(),
+        });
         let headers = if reader.has_headers() {
             Some(reader.headers()?.to_owned())
         } else {
             None
         };
-        Ok(CsvIonIterator::<R> { reader, headers })
+        Ok(CsvIonIterator::<R> {
+            reader,
+            headers,
+            bytes,
+            offset: 0,
+            line: String::new(),
+        })
     }
 
     fn get_header(&self, index: usize) -> Option<&str> {
@@ -27,14 +58,35 @@ impl<R: Read> CsvIonIterator<R> {
             None
         }
     }
+
+    /// The text of the last record returned, as it was read and without its
+    /// line terminator. Quoted fields keep their quotes.
+    pub fn line(&self) -> &str {
+        &self.line
+    }
+
+    /// Takes the text of the record starting at byte `start` of the input
+    /// and ending where the reader now is, and forgets everything before it.
+    fn take_line(&mut self, start: u64) {
+        let end = self.reader.position().byte();
+        let mut bytes = self.bytes.borrow_mut();
+        let (start, end) = ((start - self.offset) as usize, (end - self.offset) as usize);
+        let text = String::from_utf8_lossy(&bytes[start..end]);
+        self.line = text.trim_matches(['\r', '\n']).to_string();
+        bytes.drain(..end);
+        self.offset += end as u64;
+    }
 }
 
 impl<R: Read> Iterator for CsvIonIterator<R> {
     type Item = Element;
 
     fn next(&mut self) -> Option<Element> {
-        match self.reader.records().next() {
-            Some(Ok(record)) => {
+        let mut record = StringRecord::new();
+        match self.reader.read_record(&mut record) {
+            Ok(true) => {
+                let start = record.position().map_or(self.offset, |p| p.byte());
+                self.take_line(start);
                 let mut builder = Element::struct_builder();
                 for (i, field) in record.iter().enumerate() {
                     let default_name = format!("{i}");
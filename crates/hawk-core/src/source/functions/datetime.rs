@@ -0,0 +1,392 @@
+use anyhow::{anyhow, Result};
+use chrono::{
+    format::{Item, StrftimeItems},
+    DateTime, Datelike, Duration, FixedOffset, Months, NaiveDate, NaiveDateTime, TimeZone,
+    Timelike, Utc,
+};
+use ion_rs::{
+    element::Value,
+    types::{Str, Timestamp},
+};
+
+use super::{int_arg, text_arg, Function, FunctionRegistry, ParamType};
+use crate::source::Context;
+
+/// Reads a timestamp argument as a chrono date-time. Timestamps with an
+/// unknown offset (`-00:00`, or date-only Ion timestamps) are taken as UTC.
+fn datetime_arg(args: &[Value], position: usize) -> Result<DateTime<FixedOffset>> {
+    let timestamp = match args.get(position) {
+        Some(Value::Timestamp(v)) => v.clone(),
+        _ => return Err(anyhow!("Missing timestamp argument {}", position + 1)),
+    };
+    match timestamp.offset() {
+        Some(_) => Ok(timestamp.try_into()?),
+        None => {
+            let datetime: NaiveDateTime = timestamp.try_into()?;
+            Ok(datetime.and_utc().fixed_offset())
+        }
+    }
+}
+
+/// Builds a timestamp at the date-time's offset, with fractional seconds
+/// only when it has any.
+fn timestamp(datetime: DateTime<FixedOffset>) -> Result<Value> {
+    let builder = Timestamp::with_ymd_hms(
+        u32::try_from(datetime.year()).map_err(|_| anyhow!("{datetime} is out of range"))?,
+        datetime.month(),
+        datetime.day(),
+        datetime.hour(),
+        datetime.minute(),
+        datetime.second(),
+    );
+    let builder = match datetime.nanosecond() {
+        0 => builder,
+        v if v % 1_000_000 == 0 => builder.with_milliseconds(v / 1_000_000),
+        v if v % 1_000 == 0 => builder.with_microseconds(v / 1_000),
+        v => builder.with_nanoseconds(v),
+    };
+    let offset = datetime.offset().local_minus_utc() / 60;
+    Ok(Value::Timestamp(builder.build_at_offset(offset)?))
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Unit {
+    Year,
+    Quarter,
+    Month,
+    Week,
+    Day,
+    Hour,
+    Minute,
+    Second,
+}
+
+fn unit_arg(args: &[Value], position: usize) -> Result<Unit> {
+    let unit = text_arg(args, position)?;
+    match unit.to_lowercase().trim_end_matches('s') {
+        "year" => Ok(Unit::Year),
+        "quarter" => Ok(Unit::Quarter),
+        "month" => Ok(Unit::Month),
+        "week" => Ok(Unit::Week),
+        "day" => Ok(Unit::Day),
+        "hour" => Ok(Unit::Hour),
+        "minute" => Ok(Unit::Minute),
+        "second" => Ok(Unit::Second),
+        _ => Err(anyhow!("Unknown date unit \"{unit}\"")),
+    }
+}
+
+impl Unit {
+    /// The length of the unit, for units that always have the same length.
+    fn duration(self) -> Option<Duration> {
+        match self {
+            Unit::Week => Some(Duration::weeks(1)),
+            Unit::Day => Some(Duration::days(1)),
+            Unit::Hour => Some(Duration::hours(1)),
+            Unit::Minute => Some(Duration::minutes(1)),
+            Unit::Second => Some(Duration::seconds(1)),
+            Unit::Year | Unit::Quarter | Unit::Month => None,
+        }
+    }
+
+    fn months(self) -> u32 {
+        match self {
+            Unit::Year => 12,
+            Unit::Quarter => 3,
+            _ => 1,
+        }
+    }
+}
+
+fn now(_: &Context, _: &[Value]) -> Result<Value> {
+    timestamp(Utc::now().fixed_offset())
+}
+
+/// `date_trunc(unit, ts)` rounds `ts` down to the start of its year,
+/// quarter, month, week (starting Monday), day, hour, minute or second.
+fn date_trunc(_: &Context, args: &[Value]) -> Result<Value> {
+    let unit = unit_arg(args, 0)?;
+    let datetime = datetime_arg(args, 1)?;
+    let date = datetime.date_naive();
+    let date = match unit {
+        Unit::Year => date.with_ordinal(1),
+        Unit::Quarter => date
+            .with_day(1)
+            .and_then(|d| d.with_month0(d.month0() / 3 * 3)),
+        Unit::Month => date.with_day(1),
+        Unit::Week => Some(date - Duration::days(date.weekday().num_days_from_monday().into())),
+        _ => Some(date),
+    }
+    .ok_or_else(|| anyhow!("Cannot truncate {datetime}"))?;
+    let (hour, minute, second) = match unit {
+        Unit::Hour => (datetime.hour(), 0, 0),
+        Unit::Minute => (datetime.hour(), datetime.minute(), 0),
+        Unit::Second => (datetime.hour(), datetime.minute(), datetime.second()),
+        _ => (0, 0, 0),
+    };
+    let truncated = date
+        .and_hms_opt(hour, minute, second)
+        .and_then(|v| datetime.offset().from_local_datetime(&v).single())
+        .ok_or_else(|| anyhow!("Cannot truncate {datetime}"))?;
+    timestamp(truncated)
+}
+
+/// `date_add(unit, amount, ts)` moves `ts` by `amount` units. Adding months
+/// keeps the day of the month where it can, clamping to the month's end.
+fn date_add(_: &Context, args: &[Value]) -> Result<Value> {
+    let unit = unit_arg(args, 0)?;
+    let amount = int_arg(args, 1)?;
+    let datetime = datetime_arg(args, 2)?;
+    let moved = match unit.duration() {
+        Some(duration) => i32::try_from(amount)
+            .ok()
+            .and_then(|amount| duration.checked_mul(amount))
+            .and_then(|duration| datetime.checked_add_signed(duration)),
+        None => {
+            let months = u32::try_from(amount.unsigned_abs())
+                .ok()
+                .and_then(|amount| amount.checked_mul(unit.months()))
+                .map(Months::new);
+            match months {
+                Some(months) if amount < 0 => datetime.checked_sub_months(months),
+                Some(months) => datetime.checked_add_months(months),
+                None => None,
+            }
+        }
+    }
+    .ok_or_else(|| anyhow!("date_add() result is out of range"))?;
+    timestamp(moved)
+}
+
+/// `date_diff(unit, start, end)` counts the whole units from `start` to
+/// `end`; it is negative when `end` comes first.
+fn date_diff(_: &Context, args: &[Value]) -> Result<Value> {
+    let unit = unit_arg(args, 0)?;
+    let (start, end) = (datetime_arg(args, 1)?, datetime_arg(args, 2)?);
+    let difference = match unit.duration() {
+        // Whole seconds are the finest unit, and unlike nanoseconds they
+        // cannot overflow for any span of timestamps.
+        Some(duration) => (end - start).num_seconds() / duration.num_seconds(),
+        None => {
+            let (start, end) = (start.naive_utc(), end.naive_utc());
+            let months = |v: &NaiveDateTime| i64::from(v.year()) * 12 + i64::from(v.month0());
+            let mut difference = months(&end) - months(&start);
+            // Only count the last month once its day and time have been reached.
+            let reached = |from: &NaiveDateTime, to: &NaiveDateTime| {
+                (to.day(), to.time()) >= (from.day(), from.time())
+            };
+            if difference > 0 && !reached(&start, &end) {
+                difference -= 1;
+            } else if difference < 0 && !reached(&end, &start) {
+                difference += 1;
+            }
+            difference / i64::from(unit.months())
+        }
+    };
+    Ok(Value::from(difference))
+}
+
+/// `extract(field, ts)` returns one part of `ts` as an integer: `year`,
+/// `quarter`, `month`, `week` (ISO week number), `day`, `dayofweek` (1 for
+/// Monday through 7), `dayofyear`, `hour`, `minute`, `second` or `epoch`.
+fn extract(_: &Context, args: &[Value]) -> Result<Value> {
+    let field = text_arg(args, 0)?;
+    let datetime = datetime_arg(args, 1)?;
+    let value = match field.to_lowercase().as_str() {
+        "year" => i64::from(datetime.year()),
+        "quarter" => i64::from(datetime.month0() / 3 + 1),
+        "month" => i64::from(datetime.month()),
+        "week" => i64::from(datetime.iso_week().week()),
+        "day" => i64::from(datetime.day()),
+        "dayofweek" => i64::from(datetime.weekday().number_from_monday()),
+        "dayofyear" => i64::from(datetime.ordinal()),
+        "hour" => i64::from(datetime.hour()),
+        "minute" => i64::from(datetime.minute()),
+        "second" => i64::from(datetime.second()),
+        "epoch" => datetime.timestamp(),
+        _ => return Err(anyhow!("Unknown date field \"{field}\"")),
+    };
+    Ok(Value::from(value))
+}
+
+/// Reads `text` using chrono's strftime-style `format`. Formats without an
+/// offset are read as UTC, and date-only formats as midnight.
+pub fn parse_timestamp(text: &str, format: &str) -> Result<Value> {
+    let datetime = DateTime::parse_from_str(text, format)
+        .or_else(|_| {
+            NaiveDateTime::parse_from_str(text, format).map(|v| v.and_utc().fixed_offset())
+        })
+        .or_else(|_| {
+            NaiveDate::parse_from_str(text, format)
+                .map(|v| v.and_hms_opt(0, 0, 0).unwrap().and_utc().fixed_offset())
+        })
+        .map_err(|e| anyhow!("Cannot read \"{text}\" as \"{format}\": {e}"))?;
+    timestamp(datetime)
+}
+
+/// Writes the timestamp `value` using a strftime-style `format`.
+pub fn format_timestamp(value: &Value, format: &str) -> Result<String> {
+    let datetime = datetime_arg(std::slice::from_ref(value), 0)?;
+    let items: Vec<_> = StrftimeItems::new(format).collect();
+    if items.contains(&Item::Error) {
+        return Err(anyhow!("Invalid date format \"{format}\""));
+    }
+    Ok(datetime.format_with_items(items.into_iter()).to_string())
+}
+
+/// Converts seconds since 1970-01-01T00:00Z to a UTC timestamp.
+pub fn epoch_timestamp(seconds: i64) -> Result<Value> {
+    let datetime =
+        DateTime::from_timestamp(seconds, 0).ok_or_else(|| anyhow!("{seconds} is out of range"))?;
+    timestamp(datetime.fixed_offset())
+}
+
+/// `strptime(s, format)`; see [`parse_timestamp`].
+fn strptime(_: &Context, args: &[Value]) -> Result<Value> {
+    parse_timestamp(text_arg(args, 0)?, text_arg(args, 1)?)
+}
+
+/// `strftime(format, ts)` writes `ts` using a strftime-style `format`.
+fn strftime(_: &Context, args: &[Value]) -> Result<Value> {
+    let text = format_timestamp(&args[1], text_arg(args, 0)?)?;
+    Ok(Value::String(Str::from(text)))
+}
+
+/// `to_epoch(ts)` is the number of seconds from 1970-01-01T00:00Z to `ts`.
+fn to_epoch(_: &Context, args: &[Value]) -> Result<Value> {
+    Ok(Value::from(datetime_arg(args, 0)?.timestamp()))
+}
+
+/// `from_epoch(seconds)` is the UTC timestamp `seconds` after the epoch.
+fn from_epoch(_: &Context, args: &[Value]) -> Result<Value> {
+    epoch_timestamp(int_arg(args, 0)?)
+}
+
+pub fn register(registry: &mut FunctionRegistry) {
+    use ParamType::*;
+    registry.register("now", Function::new(&[], now));
+    registry.register("date_trunc", Function::new(&[Text, Timestamp], date_trunc));
+    registry.register(
+        "date_add",
+        Function::new(&[Text, Integer, Timestamp], date_add),
+    );
+    registry.register(
+        "date_diff",
+        Function::new(&[Text, Timestamp, Timestamp], date_diff),
+    );
+    registry.register("extract", Function::new(&[Text, Timestamp], extract));
+    registry.register("strptime", Function::new(&[Text, Text], strptime));
+    registry.register("strftime", Function::new(&[Text, Timestamp], strftime));
+    registry.register("to_epoch", Function::new(&[Timestamp], to_epoch));
+    registry.register("from_epoch", Function::new(&[Integer], from_epoch));
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::source::{functions::resolve_fields, resolve_timestamp, Context};
+    use ion_rs::element::Value;
+
+    fn resolve(input: &str) -> anyhow::Result<Value> {
+        resolve_fields(
+            &Context::default(),
+            &["2024-01-31T22:15:30+02:00", "15/03/2024", "2024-02-29"],
+            input,
+        )
+    }
+
+    fn timestamp(text: &str) -> Value {
+        resolve_timestamp(text).unwrap()
+    }
+
+    #[test]
+    fn test_datetime_functions() {
+        assert_eq!(
+            resolve("date_trunc(\"month\", $1)").unwrap(),
+            timestamp("2024-01-01T00:00:00+02:00")
+        );
+        assert_eq!(
+            resolve("date_trunc(`hour`, $1)").unwrap(),
+            timestamp("2024-01-31T22:00:00+02:00")
+        );
+        assert_eq!(
+            resolve("date_trunc(\"week\", $3)").unwrap(),
+            timestamp("2024-02-26T00:00:00Z")
+        );
+        assert_eq!(
+            resolve("date_trunc(\"quarter\", 2024-05-17T)").unwrap(),
+            timestamp("2024-04-01T00:00:00Z")
+        );
+        assert_eq!(
+            resolve("date_add(\"month\", 1, $1)").unwrap(),
+            timestamp("2024-02-29T22:15:30+02:00")
+        );
+        assert_eq!(
+            resolve("date_add(\"years\", -1, $3)").unwrap(),
+            timestamp("2023-02-28T00:00:00Z")
+        );
+        assert_eq!(
+            resolve("date_add(\"hour\", 2, $1)").unwrap(),
+            timestamp("2024-02-01T00:15:30+02:00")
+        );
+        assert_eq!(
+            resolve("date_diff(\"day\", $1, $3)").unwrap(),
+            Value::from(28)
+        );
+        assert_eq!(
+            resolve("date_diff(\"day\", 1700-01-01T, 2024-01-01T)").unwrap(),
+            Value::from(118338)
+        );
+        assert_eq!(
+            resolve("date_diff(\"week\", 2024-01-01T, 1700-01-01T)").unwrap(),
+            Value::from(-16905)
+        );
+        assert_eq!(
+            resolve("date_diff(\"second\", 2024-01-01T00:00:01.5Z, 2024-01-01T)").unwrap(),
+            Value::from(-1)
+        );
+        assert_eq!(
+            resolve("date_diff(\"month\", $1, $3)").unwrap(),
+            Value::from(0)
+        );
+        assert_eq!(
+            resolve("date_diff(\"month\", $3, $1)").unwrap(),
+            Value::from(0)
+        );
+        assert_eq!(
+            resolve("date_diff(\"month\", 2024-01-15, 2024-03-15)").unwrap(),
+            Value::from(2)
+        );
+        assert_eq!(resolve("extract(\"year\", $1)").unwrap(), Value::from(2024));
+        assert_eq!(
+            resolve("extract(\"dayofweek\", $3)").unwrap(),
+            Value::from(4)
+        );
+        assert_eq!(resolve("extract(\"hour\", $1)").unwrap(), Value::from(22));
+        assert_eq!(
+            resolve("strptime($2, \"%d/%m/%Y\")").unwrap(),
+            timestamp("2024-03-15T00:00:00Z")
+        );
+        assert_eq!(
+            resolve("strptime(\"2024-03-15 08:30 +0100\", \"%Y-%m-%d %H:%M %z\")").unwrap(),
+            timestamp("2024-03-15T08:30:00+01:00")
+        );
+        assert_eq!(
+            resolve("strftime(\"%d %b %Y %H:%M\", $1)").unwrap(),
+            Value::from("31 Jan 2024 22:15")
+        );
+        assert_eq!(resolve("to_epoch($3)").unwrap(), Value::from(1709164800));
+        assert_eq!(
+            resolve("from_epoch(1709164800)").unwrap(),
+            timestamp("2024-02-29T00:00:00Z")
+        );
+        assert_eq!(
+            resolve("$1 > date_add(\"day\", -1, now())").unwrap(),
+            Value::Bool(false)
+        );
+
+        assert!(resolve("date_trunc(\"fortnight\", $1)").is_err());
+        assert!(resolve("extract(\"year\", $2)").is_err());
+        assert!(resolve("strptime($2, \"%Y-%m-%d\")").is_err());
+        assert!(resolve("strftime(\"%Q\", $1)").is_err());
+    }
+}
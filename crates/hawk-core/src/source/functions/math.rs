@@ -0,0 +1,312 @@
+use anyhow::{anyhow, Result};
+use ion_rs::{
+    element::Value,
+    external::bigdecimal::{BigDecimal, One, Signed, Zero},
+    types::{Decimal, Int},
+};
+use rand::Rng;
+use std::cmp::Ordering;
+
+use super::{int_arg, Function, FunctionRegistry, ParamType};
+use crate::source::{Context, ValueArithmetic, ValueComparison};
+
+fn decimal(value: BigDecimal) -> Value {
+    Value::Decimal(Decimal::from(value))
+}
+
+fn abs(_: &Context, args: &[Value]) -> Result<Value> {
+    Ok(match &args[0] {
+        Value::Int(v) => ValueArithmetic::from_big_int(ValueArithmetic::big_int(v).abs()),
+        Value::Float(v) => Value::Float(v.abs()),
+        Value::Decimal(v) => decimal(ValueArithmetic::big_decimal(v).abs()),
+        value => return Err(anyhow!("{value} is not a number")),
+    })
+}
+
+/// `round(x, digits?)` rounds half away from zero to `digits` places after
+/// the point (0 by default); negative `digits` round to tens, hundreds, ...
+fn round(_: &Context, args: &[Value]) -> Result<Value> {
+    let digits = match args.get(1) {
+        Some(_) => int_arg(args, 1)?,
+        None => 0,
+    };
+    Ok(match &args[0] {
+        Value::Int(_) if digits >= 0 => args[0].clone(),
+        Value::Int(v) => {
+            let rounded = BigDecimal::new(ValueArithmetic::big_int(v), 0).round(digits);
+            ValueArithmetic::from_big_int(rounded.with_scale(0).as_bigint_and_exponent().0)
+        }
+        Value::Float(v) => {
+            let scale = 10f64.powi(i32::try_from(digits)?);
+            Value::Float((v * scale).round() / scale)
+        }
+        Value::Decimal(v) => decimal(ValueArithmetic::big_decimal(v).round(digits)),
+        value => return Err(anyhow!("{value} is not a number")),
+    })
+}
+
+/// Rounds a decimal to a whole number, towards negative infinity when
+/// `ceiling` is false and towards positive infinity when it is true.
+fn round_decimal(value: &Decimal, ceiling: bool) -> BigDecimal {
+    let value = ValueArithmetic::big_decimal(value);
+    let truncated = value.with_scale(0);
+    match (truncated.cmp(&value), ceiling) {
+        (std::cmp::Ordering::Greater, false) => truncated - BigDecimal::one(),
+        (std::cmp::Ordering::Less, true) => truncated + BigDecimal::one(),
+        _ => truncated,
+    }
+}
+
+fn floor(_: &Context, args: &[Value]) -> Result<Value> {
+    Ok(match &args[0] {
+        Value::Int(_) => args[0].clone(),
+        Value::Float(v) => Value::Float(v.floor()),
+        Value::Decimal(v) => decimal(round_decimal(v, false)),
+        value => return Err(anyhow!("{value} is not a number")),
+    })
+}
+
+fn ceil(_: &Context, args: &[Value]) -> Result<Value> {
+    Ok(match &args[0] {
+        Value::Int(_) => args[0].clone(),
+        Value::Float(v) => Value::Float(v.ceil()),
+        Value::Decimal(v) => decimal(round_decimal(v, true)),
+        value => return Err(anyhow!("{value} is not a number")),
+    })
+}
+
+fn sqrt(_: &Context, args: &[Value]) -> Result<Value> {
+    Ok(Value::Float(ValueArithmetic::to_f64(&args[0])?.sqrt()))
+}
+
+/// `log(x, base?)` is the natural logarithm of `x`, or its logarithm in
+/// `base`.
+fn log(_: &Context, args: &[Value]) -> Result<Value> {
+    let value = ValueArithmetic::to_f64(&args[0])?;
+    Ok(Value::Float(match args.get(1) {
+        Some(base) => value.log(ValueArithmetic::to_f64(base)?),
+        None => value.ln(),
+    }))
+}
+
+fn exp(_: &Context, args: &[Value]) -> Result<Value> {
+    Ok(Value::Float(ValueArithmetic::to_f64(&args[0])?.exp()))
+}
+
+/// The most bits the mantissa of an exact `pow()` result may need; larger
+/// results are computed as floats instead.
+const MAX_EXACT_POW_BITS: u64 = 4096;
+
+/// `base` raised to `power` exactly, or `None` when the result would be
+/// larger than [`MAX_EXACT_POW_BITS`] allows.
+fn exact_power(base: &BigDecimal, power: u32) -> Option<BigDecimal> {
+    let (mantissa, scale) = base.as_bigint_and_exponent();
+    let bits = (mantissa.bits().saturating_sub(1)).saturating_mul(u64::from(power));
+    if bits > MAX_EXACT_POW_BITS {
+        return None;
+    }
+    Some(BigDecimal::new(
+        mantissa.pow(power),
+        scale.checked_mul(i64::from(power))?,
+    ))
+}
+
+/// `pow(x, y)`. Integer and decimal bases raised to an integer power stay
+/// exact (a negative power gives a decimal) while the result is of a
+/// reasonable size; anything else is a float.
+fn pow(_: &Context, args: &[Value]) -> Result<Value> {
+    let (base, exponent) = (&args[0], &args[1]);
+    let power = match exponent {
+        Value::Int(Int::I64(v)) => u32::try_from(v.unsigned_abs()).ok(),
+        _ => None,
+    };
+    let negative = matches!(exponent, Value::Int(v) if ValueArithmetic::big_int(v).is_negative());
+    let exact = match (base, power) {
+        (Value::Int(v), Some(power)) => {
+            exact_power(&BigDecimal::new(ValueArithmetic::big_int(v), 0), power)
+        }
+        (Value::Decimal(v), Some(power)) => exact_power(&ValueArithmetic::big_decimal(v), power),
+        _ => None,
+    };
+    let exact = match exact {
+        Some(exact) => exact,
+        None => {
+            return Ok(Value::Float(
+                ValueArithmetic::to_f64(base)?.powf(ValueArithmetic::to_f64(exponent)?),
+            ))
+        }
+    };
+    match (negative, base) {
+        (false, Value::Int(_)) => Ok(ValueArithmetic::from_big_int(
+            exact.as_bigint_and_exponent().0,
+        )),
+        (false, _) => Ok(decimal(exact)),
+        (true, _) if exact.is_zero() => Err(anyhow!("Division by zero")),
+        (true, _) => Ok(decimal(BigDecimal::one() / exact)),
+    }
+}
+
+/// Picks the least (or, with `greatest`, the largest) argument, comparing
+/// them the way `<` does.
+fn extreme(ctx: &Context, args: &[Value], greatest: bool) -> Result<Value> {
+    let mut best = &args[0];
+    for candidate in &args[1..] {
+        let (lhs, rhs) = ctx.coercion.coerce(candidate, best)?;
+        let wanted = if greatest {
+            Ordering::Greater
+        } else {
+            Ordering::Less
+        };
+        if ValueComparison::compare(&lhs, &rhs) == Some(wanted) {
+            best = candidate;
+        }
+    }
+    Ok(best.clone())
+}
+
+fn min(ctx: &Context, args: &[Value]) -> Result<Value> {
+    extreme(ctx, args, false)
+}
+
+fn max(ctx: &Context, args: &[Value]) -> Result<Value> {
+    extreme(ctx, args, true)
+}
+
+/// `rand()` is a float in `[0, 1)`. Runs given a seed (`--seed`) produce
+/// the same sequence every time.
+fn rand(ctx: &Context, _: &[Value]) -> Result<Value> {
+    Ok(Value::Float(ctx.rng().gen()))
+}
+
+pub fn register(registry: &mut FunctionRegistry) {
+    use ParamType::*;
+    registry.register("abs", Function::new(&[Number], abs));
+    registry.register(
+        "round",
+        Function::new(&[Number, Integer], round).optional(1),
+    );
+    registry.register("floor", Function::new(&[Number], floor));
+    registry.register("ceil", Function::new(&[Number], ceil));
+    registry.register("sqrt", Function::new(&[Number], sqrt));
+    registry.register("pow", Function::new(&[Number, Number], pow));
+    registry.register("log", Function::new(&[Number, Number], log).optional(1));
+    registry.register("exp", Function::new(&[Number], exp));
+    registry.register("min", Function::new(&[Number], min).variadic());
+    registry.register("max", Function::new(&[Number], max).variadic());
+    registry.register("rand", Function::new(&[], rand));
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::source::{functions::resolve_fields, Context};
+    use ion_rs::{
+        element::Value,
+        external::bigdecimal::num_bigint::BigInt,
+        types::{Decimal, Int},
+    };
+
+    fn resolve_with(ctx: &Context, input: &str) -> anyhow::Result<Value> {
+        resolve_fields(ctx, &["-7", "2.675", "16"], input)
+    }
+
+    fn resolve(input: &str) -> anyhow::Result<Value> {
+        resolve_with(&Context::default(), input)
+    }
+
+    #[test]
+    fn test_math_functions() {
+        assert_eq!(resolve("abs($1)").unwrap(), Value::from(7));
+        assert_eq!(resolve("abs(-2.5e0)").unwrap(), Value::Float(2.5));
+        assert_eq!(
+            resolve("abs(-9223372036854775807 - 1)").unwrap(),
+            Value::Int(Int::BigInt(-BigInt::from(i64::MIN)))
+        );
+        assert_eq!(
+            resolve("abs(-1.50)").unwrap(),
+            Value::Decimal(Decimal::new(150, -2))
+        );
+        assert_eq!(
+            resolve("round($2, 2)").unwrap(),
+            Value::Decimal(Decimal::new(268, -2))
+        );
+        assert_eq!(
+            resolve("round($2)").unwrap(),
+            Value::Decimal(Decimal::new(3, 0))
+        );
+        assert_eq!(resolve("round(1250, -2)").unwrap(), Value::from(1300));
+        assert_eq!(resolve("round(1.25e0, 1)").unwrap(), Value::Float(1.3));
+        assert_eq!(
+            resolve("floor(-$2)").unwrap(),
+            Value::Decimal(Decimal::new(-3, 0))
+        );
+        assert_eq!(
+            resolve("ceil($2)").unwrap(),
+            Value::Decimal(Decimal::new(3, 0))
+        );
+        assert_eq!(resolve("floor(2.5e0)").unwrap(), Value::Float(2.0));
+        assert_eq!(resolve("ceil($1)").unwrap(), Value::from(-7));
+        assert_eq!(resolve("sqrt($3)").unwrap(), Value::Float(4.0));
+        assert_eq!(resolve("log(8, 2)").unwrap(), Value::Float(3.0));
+        assert_eq!(resolve("exp(0)").unwrap(), Value::Float(1.0));
+        assert_eq!(resolve("pow(2, 10)").unwrap(), Value::from(1024));
+        assert_eq!(
+            resolve("pow(2, 64)").unwrap(),
+            Value::Int(Int::BigInt(BigInt::from(2).pow(64)))
+        );
+        assert_eq!(
+            resolve("pow(1.5, 2)").unwrap(),
+            Value::Decimal(Decimal::new(225, -2))
+        );
+        assert_eq!(
+            resolve("pow(2, -2)").unwrap(),
+            Value::Decimal(Decimal::new(25, -2))
+        );
+        assert_eq!(resolve("pow($3, 0.5)").unwrap(), Value::Float(4.0));
+        assert_eq!(resolve("min($3, 3, $1)").unwrap(), Value::from(-7));
+        assert_eq!(
+            resolve("max($2, 2, 2.7)").unwrap(),
+            Value::Decimal(Decimal::new(27, -1))
+        );
+        assert_eq!(resolve("max(1)").unwrap(), Value::from(1));
+
+        assert!(resolve("min()").is_err());
+        assert!(resolve("abs(\"x\")").is_err());
+        assert!(resolve("pow(0, -1)").is_err());
+        // Results too large to keep exact are computed as floats.
+        assert_eq!(
+            resolve("pow(2, 4096)").unwrap(),
+            Value::Int(Int::BigInt(BigInt::from(2).pow(4096)))
+        );
+        assert_eq!(
+            resolve("pow(2, 4000000000)").unwrap(),
+            Value::Float(f64::INFINITY)
+        );
+        assert!(matches!(
+            resolve("pow(1.1, 200000)").unwrap(),
+            Value::Float(v) if v.is_infinite()
+        ));
+        assert_eq!(
+            resolve("pow(0.5, -4000000000)").unwrap(),
+            Value::Float(f64::INFINITY)
+        );
+    }
+
+    #[test]
+    fn test_rand() {
+        let first = Context::default();
+        first.seed(42);
+        let second = Context::default();
+        second.seed(42);
+        let draws = |ctx| {
+            (0..3)
+                .map(|_| resolve_with(ctx, "rand()").unwrap())
+                .collect::<Vec<_>>()
+        };
+        let values = draws(&first);
+        assert_eq!(values, draws(&second));
+        assert_ne!(values[0], values[1]);
+        for value in values {
+            assert!(matches!(value, Value::Float(v) if (0.0..1.0).contains(&v)));
+        }
+    }
+}
@@ -0,0 +1,287 @@
+use anyhow::{anyhow, Result};
+use ion_rs::{
+    element::Value,
+    types::{Int, IonType, Str},
+};
+use std::{borrow::Cow, collections::HashMap, fmt};
+
+use super::{resolve_timestamp, Context, ValueArithmetic, ValueImplicitConversion};
+use crate::template::value_text;
+
+pub mod datetime;
+pub mod math;
+pub mod string;
+
+/// The kind of value a function parameter accepts. Arguments are converted
+/// to that kind before the function is called, so CSV fields can be passed
+/// wherever numbers or timestamps are expected.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ParamType {
+    /// Any value, passed through unchanged.
+    Any,
+    /// A string; symbols and scalars are passed as their text.
+    Text,
+    /// An Int, Float or Decimal.
+    Number,
+    /// An Int.
+    Integer,
+    /// A timestamp; strings are read as RFC 3339 or Ion timestamps.
+    Timestamp,
+}
+
+impl fmt::Display for ParamType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            ParamType::Any => "a value",
+            ParamType::Text => "text",
+            ParamType::Number => "a number",
+            ParamType::Integer => "an integer",
+            ParamType::Timestamp => "a timestamp",
+        })
+    }
+}
+
+impl ParamType {
+    fn convert(self, value: Value) -> Option<Value> {
+        match (self, value) {
+            (ParamType::Any, value) => Some(value),
+            (ParamType::Text, value @ Value::String(_)) => Some(value),
+            (
+                ParamType::Text,
+                value @ (Value::Symbol(_)
+                | Value::Bool(_)
+                | Value::Int(_)
+                | Value::Float(_)
+                | Value::Decimal(_)
+                | Value::Timestamp(_)),
+            ) => Some(Value::String(Str::from(value_text(&value)))),
+            (ParamType::Number, value) => {
+                ValueArithmetic::to_number(&value).ok().map(Cow::into_owned)
+            }
+            (ParamType::Integer, value) => {
+                match ValueArithmetic::to_number(&value).ok()?.into_owned() {
+                    number @ Value::Int(_) => Some(number),
+                    _ => None,
+                }
+            }
+            (ParamType::Timestamp, value @ Value::Timestamp(_)) => Some(value),
+            (ParamType::Timestamp, value @ Value::String(_)) => {
+                ValueImplicitConversion::coerce_value(&value, IonType::Timestamp)
+                    .ok()
+                    .map(Cow::into_owned)
+                    .or_else(|| resolve_timestamp(&value_text(&value)).ok())
+            }
+            _ => None,
+        }
+    }
+}
+
+pub type FunctionImpl = fn(&Context, &[Value]) -> Result<Value>;
+
+/// A built-in function: its parameters and the Rust code that implements it.
+/// The last `optional` parameters may be left out; a `variadic` function
+/// takes any number of further arguments of its last parameter's type.
+#[derive(Debug, Clone)]
+pub struct Function {
+    pub params: &'static [ParamType],
+    pub optional: usize,
+    pub variadic: bool,
+    pub implementation: FunctionImpl,
+}
+
+impl Function {
+    pub fn new(params: &'static [ParamType], implementation: FunctionImpl) -> Self {
+        Function {
+            params,
+            optional: 0,
+            variadic: false,
+            implementation,
+        }
+    }
+
+    pub fn optional(mut self, optional: usize) -> Self {
+        self.optional = optional;
+        self
+    }
+
+    pub fn variadic(mut self) -> Self {
+        self.variadic = true;
+        self
+    }
+
+    fn check_arity(&self, name: &str, count: usize) -> Result<()> {
+        let required = self.params.len() - self.optional;
+        let accepted = if self.variadic {
+            count >= required
+        } else {
+            (required..=self.params.len()).contains(&count)
+        };
+        if accepted {
+            return Ok(());
+        }
+        let expected = match (self.variadic, required == self.params.len()) {
+            (true, _) => format!("at least {required}"),
+            (false, true) => required.to_string(),
+            (false, false) => format!("{required} to {}", self.params.len()),
+        };
+        Err(anyhow!(
+            "{name}() takes {expected} argument(s) but {count} were given"
+        ))
+    }
+
+    fn param_type(&self, position: usize) -> ParamType {
+        self.params
+            .get(position)
+            .or(self.params.last())
+            .copied()
+            .unwrap_or(ParamType::Any)
+    }
+}
+
+/// Maps function names to their implementations. New functions are added
+/// here rather than to the grammar, which parses any `name(args...)`.
+#[derive(Debug, Clone)]
+pub struct FunctionRegistry {
+    functions: HashMap<String, Function>,
+}
+
+impl Default for FunctionRegistry {
+    /// A registry holding the built-in functions.
+    fn default() -> Self {
+        let mut registry = FunctionRegistry {
+            functions: HashMap::new(),
+        };
+        string::register(&mut registry);
+        datetime::register(&mut registry);
+        math::register(&mut registry);
+        registry
+    }
+}
+
+impl FunctionRegistry {
+    pub fn register(&mut self, name: &str, function: Function) {
+        self.functions.insert(name.to_string(), function);
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Function> {
+        self.functions.get(name)
+    }
+
+    /// Calls `name` after checking the number of arguments and converting
+    /// each to the type its parameter accepts. Like the operators, a function
+    /// given null (or a missing field) for a typed parameter returns null
+    /// without being called; only `Any` parameters see nulls.
+    pub fn call(&self, ctx: &Context, name: &str, args: Vec<Value>) -> Result<Value> {
+        let function = self
+            .get(name)
+            .ok_or_else(|| anyhow!("Unknown function {name}()"))?;
+        function.check_arity(name, args.len())?;
+
+        let args = args
+            .into_iter()
+            .enumerate()
+            .map(|(i, arg)| {
+                let param_type = function.param_type(i);
+                if param_type != ParamType::Any && matches!(arg, Value::Null(_)) {
+                    return Ok(None);
+                }
+                let text = arg.to_string();
+                param_type.convert(arg).map(Some).ok_or_else(|| {
+                    anyhow!(
+                        "{name}() argument {} must be {param_type}, not {text}",
+                        i + 1
+                    )
+                })
+            })
+            .collect::<Result<Option<Vec<_>>>>()?;
+        match args {
+            Some(args) => (function.implementation)(ctx, &args),
+            None => Ok(Value::Null(IonType::Null)),
+        }
+    }
+}
+
+/// Returns the integer argument at `position`, which the registry has
+/// already checked to be an Int.
+pub fn int_arg(args: &[Value], position: usize) -> Result<i64> {
+    match args.get(position) {
+        Some(Value::Int(Int::I64(v))) => Ok(*v),
+        Some(Value::Int(Int::BigInt(v))) => Err(anyhow!("{v} is out of range")),
+        _ => Err(anyhow!("Missing integer argument {}", position + 1)),
+    }
+}
+
+/// Returns the text argument at `position`, which the registry has already
+/// converted to a string.
+pub fn text_arg(args: &[Value], position: usize) -> Result<&str> {
+    match args.get(position) {
+        Some(Value::String(v)) => Ok(v.text()),
+        _ => Err(anyhow!("Missing text argument {}", position + 1)),
+    }
+}
+
+/// Resolves `input` against a CSV-shaped record whose fields are `fields`,
+/// so `$1` is `fields[0]`.
+#[cfg(test)]
+pub(crate) fn resolve_fields(ctx: &Context, fields: &[&str], input: &str) -> Result<Value> {
+    let mut record = ion_rs::element::Element::struct_builder();
+    for (i, field) in fields.iter().enumerate() {
+        record = record.with_field(i.to_string(), *field);
+    }
+    let record = ion_rs::element::Element::from(record.build());
+    let (_, expr) = hawk_parser::parse_expr(input).unwrap();
+    super::resolve_expr(ctx, record.as_struct().unwrap(), &expr).map(Cow::into_owned)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn repeat(_: &Context, args: &[Value]) -> Result<Value> {
+        let count = args.get(1).map_or(Ok(2), |_| int_arg(args, 1))?;
+        let text = text_arg(args, 0)?.repeat(count.max(0) as usize);
+        Ok(Value::String(Str::from(text)))
+    }
+
+    fn count(_: &Context, args: &[Value]) -> Result<Value> {
+        Ok(Value::from(args.len() as i64))
+    }
+
+    #[test]
+    fn test_call() {
+        let mut ctx = Context::default();
+        ctx.functions.register(
+            "repeat",
+            Function::new(&[ParamType::Text, ParamType::Integer], repeat).optional(1),
+        );
+        ctx.functions.register(
+            "count",
+            Function::new(&[ParamType::Any, ParamType::Number], count).variadic(),
+        );
+        let resolve = |input| resolve_fields(&ctx, &["ab", "3"], input);
+
+        assert_eq!(resolve("repeat($1, $2)").unwrap(), Value::from("ababab"));
+        assert_eq!(resolve("repeat($1)").unwrap(), Value::from("abab"));
+        assert_eq!(
+            resolve("repeat($3, 2)").unwrap(),
+            Value::Null(IonType::Null)
+        );
+        assert_eq!(
+            resolve("repeat($1, $3)").unwrap(),
+            Value::Null(IonType::Null)
+        );
+        assert_eq!(resolve("repeat(`x`, 1 + 1)").unwrap(), Value::from("xx"));
+        assert_eq!(
+            resolve("repeat(7, 2) == \"77\"").unwrap(),
+            Value::Bool(true)
+        );
+        assert_eq!(resolve("count($1, 1, $2, 2.5)").unwrap(), Value::from(4));
+
+        assert!(resolve("repeat()").is_err());
+        assert!(resolve("repeat($1, 1, 2)").is_err());
+        assert!(resolve("repeat($1, $1)").is_err());
+        assert!(resolve("repeat($1, 1.5)").is_err());
+        assert!(resolve("count($1, $1)").is_err());
+        assert!(resolve("missing($1)").is_err());
+    }
+}
@@ -0,0 +1,292 @@
+use anyhow::{anyhow, Result};
+use ion_rs::{
+    element::{Element, Sequence, Value},
+    types::{IonType, Str},
+};
+
+use super::{int_arg, text_arg, Function, FunctionRegistry, ParamType};
+use crate::{
+    source::{pattern::glob_regex, Context},
+    template::value_text,
+};
+
+fn string(text: impl Into<String>) -> Value {
+    Value::String(Str::from(text.into()))
+}
+
+/// Maps a zero-based position, negative counting back from the end, onto a
+/// char offset clamped to `0..=len`.
+fn clamp_position(position: i64, len: usize) -> usize {
+    let position = if position < 0 {
+        len as i64 + position
+    } else {
+        position
+    };
+    position.clamp(0, len as i64) as usize
+}
+
+/// The number of characters in a string or symbol, or the number of
+/// elements in a list, s-expression or struct; null for null.
+fn length(_: &Context, args: &[Value]) -> Result<Value> {
+    let length = match &args[0] {
+        Value::Null(_) => return Ok(Value::Null(IonType::Null)),
+        Value::String(v) => v.text().chars().count(),
+        Value::Symbol(v) => v.text().unwrap_or_default().chars().count(),
+        Value::List(v) | Value::SExp(v) => v.len(),
+        Value::Struct(v) => v.len(),
+        value => value_text(value).chars().count(),
+    };
+    Ok(Value::from(length as i64))
+}
+
+/// `substr(s, start, length?)`, counting characters from zero like `[N]`
+/// subscripts; a negative start counts back from the end.
+fn substr(_: &Context, args: &[Value]) -> Result<Value> {
+    let chars: Vec<char> = text_arg(args, 0)?.chars().collect();
+    let start = clamp_position(int_arg(args, 1)?, chars.len());
+    let end = match args.get(2) {
+        Some(_) => start.saturating_add(int_arg(args, 2)?.max(0) as usize),
+        None => chars.len(),
+    };
+    Ok(string(
+        chars[start..end.min(chars.len())]
+            .iter()
+            .collect::<String>(),
+    ))
+}
+
+fn upper(_: &Context, args: &[Value]) -> Result<Value> {
+    Ok(string(text_arg(args, 0)?.to_uppercase()))
+}
+
+fn lower(_: &Context, args: &[Value]) -> Result<Value> {
+    Ok(string(text_arg(args, 0)?.to_lowercase()))
+}
+
+/// `trim(s, chars?)` strips whitespace, or any of `chars`, from both ends.
+fn trim(_: &Context, args: &[Value]) -> Result<Value> {
+    let text = text_arg(args, 0)?;
+    Ok(string(match args.get(1) {
+        Some(_) => {
+            let chars = text_arg(args, 1)?;
+            text.trim_matches(|c| chars.contains(c))
+        }
+        None => text.trim(),
+    }))
+}
+
+/// `split(s, separator)` returns the pieces of `s` as a list of strings.
+fn split(_: &Context, args: &[Value]) -> Result<Value> {
+    let (text, separator) = (text_arg(args, 0)?, text_arg(args, 1)?);
+    let pieces: Vec<Element> = match separator {
+        "" => text.chars().map(|c| string(c).into()).collect(),
+        _ => text.split(separator).map(|s| string(s).into()).collect(),
+    };
+    Ok(Value::List(Sequence::new(pieces)))
+}
+
+fn substitute(ctx: &Context, args: &[Value], limit: usize) -> Result<Value> {
+    let text = text_arg(args, 0)?;
+    let regex = ctx.regex(text_arg(args, 1)?)?;
+    Ok(string(regex.replacen(text, limit, text_arg(args, 2)?)))
+}
+
+/// `replace(s, pattern, replacement)` replaces the first match of the regex
+/// `pattern`; `$1` or `${name}` in `replacement` insert its groups.
+fn replace(ctx: &Context, args: &[Value]) -> Result<Value> {
+    substitute(ctx, args, 1)
+}
+
+/// `gsub(s, pattern, replacement)` replaces every match, like `replace`.
+fn gsub(ctx: &Context, args: &[Value]) -> Result<Value> {
+    substitute(ctx, args, 0)
+}
+
+fn starts_with(_: &Context, args: &[Value]) -> Result<Value> {
+    Ok(Value::Bool(
+        text_arg(args, 0)?.starts_with(text_arg(args, 1)?),
+    ))
+}
+
+fn ends_with(_: &Context, args: &[Value]) -> Result<Value> {
+    Ok(Value::Bool(
+        text_arg(args, 0)?.ends_with(text_arg(args, 1)?),
+    ))
+}
+
+fn contains(_: &Context, args: &[Value]) -> Result<Value> {
+    Ok(Value::Bool(text_arg(args, 0)?.contains(text_arg(args, 1)?)))
+}
+
+/// The widest `pad()` accepts, so a stray field cannot exhaust memory.
+const MAX_PAD_WIDTH: u64 = 1 << 20;
+
+/// `pad(s, width, fill?)` pads `s` with `fill` (a space by default) to
+/// `width` characters. As in printf, a positive width right-aligns `s` and a
+/// negative one left-aligns it.
+fn pad(_: &Context, args: &[Value]) -> Result<Value> {
+    let text = text_arg(args, 0)?;
+    let width = int_arg(args, 1)?;
+    let fill = match args.get(2) {
+        Some(_) => text_arg(args, 2)?,
+        None => " ",
+    };
+    if fill.is_empty() {
+        return Err(anyhow!("pad() fill must not be empty"));
+    }
+    let columns = match usize::try_from(width.unsigned_abs()) {
+        Ok(columns) if width.unsigned_abs() <= MAX_PAD_WIDTH => columns,
+        _ => return Err(anyhow!("pad() width {width} is over {MAX_PAD_WIDTH}")),
+    };
+    let missing = columns.saturating_sub(text.chars().count());
+    let padding: String = fill.chars().cycle().take(missing).collect();
+    Ok(string(if width < 0 {
+        format!("{text}{padding}")
+    } else {
+        format!("{padding}{text}")
+    }))
+}
+
+fn concat(_: &Context, args: &[Value]) -> Result<Value> {
+    let texts = (0..args.len())
+        .map(|i| text_arg(args, i))
+        .collect::<Result<Vec<_>>>()?;
+    Ok(string(texts.concat()))
+}
+
+/// `index(s, t)` is the zero-based character position of `t` in `s`, or -1
+/// when it does not occur.
+fn index(_: &Context, args: &[Value]) -> Result<Value> {
+    let text = text_arg(args, 0)?;
+    let position = match text.find(text_arg(args, 1)?) {
+        Some(offset) => text[..offset].chars().count() as i64,
+        None => -1,
+    };
+    Ok(Value::from(position))
+}
+
+/// `glob(s, pattern)` tests `s` against a shell glob: `*` matches any run of
+/// characters, `?` any one, and `[...]` one character of a set.
+fn glob(ctx: &Context, args: &[Value]) -> Result<Value> {
+    let regex = ctx.regex(&glob_regex(text_arg(args, 1)?))?;
+    Ok(Value::Bool(regex.is_match(text_arg(args, 0)?)))
+}
+
+pub fn register(registry: &mut FunctionRegistry) {
+    use ParamType::*;
+    registry.register("length", Function::new(&[Any], length));
+    registry.register(
+        "substr",
+        Function::new(&[Text, Integer, Integer], substr).optional(1),
+    );
+    registry.register("upper", Function::new(&[Text], upper));
+    registry.register("lower", Function::new(&[Text], lower));
+    registry.register("trim", Function::new(&[Text, Text], trim).optional(1));
+    registry.register("split", Function::new(&[Text, Text], split));
+    registry.register("replace", Function::new(&[Text, Text, Text], replace));
+    registry.register("gsub", Function::new(&[Text, Text, Text], gsub));
+    registry.register("starts_with", Function::new(&[Text, Text], starts_with));
+    registry.register("ends_with", Function::new(&[Text, Text], ends_with));
+    registry.register("contains", Function::new(&[Text, Text], contains));
+    registry.register(
+        "pad",
+        Function::new(&[Text, Integer, Text], pad).optional(1),
+    );
+    registry.register("concat", Function::new(&[Text], concat).variadic());
+    registry.register("index", Function::new(&[Text, Text], index));
+    registry.register("glob", Function::new(&[Text, Text], glob));
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::source::{functions::resolve_fields, Context};
+    use ion_rs::{
+        element::{Element, Value},
+        types::IonType,
+    };
+
+    fn resolve(input: &str) -> anyhow::Result<Value> {
+        resolve_fields(
+            &Context::default(),
+            &["  Ada Lovelace ", "2024-01-15", "héllo"],
+            input,
+        )
+    }
+
+    #[test]
+    fn test_string_functions() {
+        assert_eq!(resolve("length($3)").unwrap(), Value::from(5));
+        assert_eq!(resolve("length(`abc`)").unwrap(), Value::from(3));
+        assert_eq!(resolve("length(12.50)").unwrap(), Value::from(5));
+        assert_eq!(resolve("substr($3, 1, 3)").unwrap(), Value::from("éll"));
+        assert_eq!(resolve("substr($3, -2)").unwrap(), Value::from("lo"));
+        assert_eq!(resolve("substr($3, 4, 10)").unwrap(), Value::from("o"));
+        assert_eq!(resolve("substr($3, 9)").unwrap(), Value::from(""));
+        assert_eq!(
+            resolve("upper(trim($1))").unwrap(),
+            Value::from("ADA LOVELACE")
+        );
+        assert_eq!(resolve("lower($3)").unwrap(), Value::from("héllo"));
+        assert_eq!(resolve("trim(\"--x-\", \"-\")").unwrap(), Value::from("x"));
+        assert_eq!(
+            &resolve("split($2, \"-\")").unwrap(),
+            Element::read_one("[\"2024\", \"01\", \"15\"]")
+                .unwrap()
+                .value()
+        );
+        assert_eq!(
+            resolve("split($2, \"-\")[1] == 1").unwrap(),
+            Value::Bool(true)
+        );
+        assert_eq!(
+            resolve("replace($2, /(\\d+)-(\\d+)/, \"$2/$1\")").unwrap(),
+            Value::from("01/2024-15")
+        );
+        assert_eq!(
+            resolve("gsub($2, \"-\", \"\")").unwrap(),
+            Value::from("20240115")
+        );
+        assert_eq!(
+            resolve("starts_with($2, \"2024\")").unwrap(),
+            Value::Bool(true)
+        );
+        assert_eq!(resolve("ends_with($3, `lo`)").unwrap(), Value::Bool(true));
+        assert_eq!(
+            resolve("contains($1, \"Love\")").unwrap(),
+            Value::Bool(true)
+        );
+        assert_eq!(resolve("pad(7, 3, 0)").unwrap(), Value::from("007"));
+        assert_eq!(
+            resolve("concat(pad($3, -7), \"|\")").unwrap(),
+            Value::from("héllo  |")
+        );
+        assert_eq!(
+            resolve("concat($3, \"-\", 1, true)").unwrap(),
+            Value::from("héllo-1true")
+        );
+        assert_eq!(resolve("index($3, \"lo\")").unwrap(), Value::from(3));
+        assert_eq!(resolve("index($3, \"x\")").unwrap(), Value::from(-1));
+
+        // A short row's missing fields make the functions return null.
+        assert_eq!(resolve("upper($9)").unwrap(), Value::Null(IonType::Null));
+        assert_eq!(resolve("length($9)").unwrap(), Value::Null(IonType::Null));
+        assert_eq!(
+            resolve("length($9) > 0").unwrap(),
+            Value::Null(IonType::Bool)
+        );
+        assert_eq!(
+            resolve("substr($3, $9)").unwrap(),
+            Value::Null(IonType::Null)
+        );
+
+        assert!(resolve("upper()").is_err());
+        assert!(resolve("substr($3, \"a\")").is_err());
+        assert!(resolve("pad($3, 9, \"\")").is_err());
+        assert!(resolve("pad($3, 9223372036854775807)").is_err());
+        assert!(resolve("pad($3, -9223372036854775807 - 1)").is_err());
+        assert_eq!(
+            resolve("length(pad($3, 1048576))").unwrap(),
+            Value::from(1048576)
+        );
+    }
+}
@@ -0,0 +1,155 @@
+use hawk_parser::Expr;
+use ion_rs::{
+    element::Value,
+    external::bigdecimal::BigDecimal,
+    types::{Decimal, Int},
+};
+use std::collections::HashSet;
+
+use super::{collation::Collation, CoercionPolicy, ValueArithmetic, ValueImplicitConversion};
+
+/// Lists with fewer members than this are searched one comparison at a time.
+pub const HASH_THRESHOLD: usize = 16;
+
+/// The members of a large `in (...)` list made only of number literals or
+/// only of string literals, hashed so each test is a single lookup. Numbers
+/// are keyed by their normalised exact value, so `1`, `1.0` and `1.00` are
+/// one key, as they are equal under `==`; strings are keyed by their
+/// [`Collation::key`].
+#[derive(Debug)]
+pub enum LiteralSet {
+    Numbers(HashSet<BigDecimal>),
+    Texts(HashSet<String>),
+}
+
+fn number_key(value: &Value) -> Option<BigDecimal> {
+    let key = match value {
+        Value::Int(Int::I64(v)) => BigDecimal::from(*v),
+        Value::Int(v) => BigDecimal::new(ValueArithmetic::big_int(v), 0),
+        Value::Decimal(v) => ValueArithmetic::big_decimal(v),
+        Value::Float(v) => ValueImplicitConversion::exact_decimal(*v)?,
+        _ => return None,
+    };
+    Some(key.normalized())
+}
+
+impl LiteralSet {
+    /// Hashes `items` when the list is large and every member is a literal
+    /// of the same kind; otherwise `None`.
+    pub fn build(items: &[Expr], collation: Collation) -> Option<LiteralSet> {
+        if items.len() < HASH_THRESHOLD {
+            return None;
+        }
+        match &items[0] {
+            Expr::String(_) => items
+                .iter()
+                .map(|item| match item {
+                    Expr::String(v) => Some(collation.key(v).into_owned()),
+                    _ => None,
+                })
+                .collect::<Option<_>>()
+                .map(LiteralSet::Texts),
+            _ => items
+                .iter()
+                .map(|item| match item {
+                    Expr::Integer(v) => Some(BigDecimal::from(*v).normalized()),
+                    Expr::Decimal(v) => {
+                        let decimal = Decimal::new(v.mantissa(), -i64::from(v.scale()));
+                        number_key(&Value::Decimal(decimal))
+                    }
+                    _ => None,
+                })
+                .collect::<Option<_>>()
+                .map(LiteralSet::Numbers),
+        }
+    }
+
+    /// Whether `value` is a member, compared as `==` would compare it, or
+    /// `None` when the answer needs the member-by-member comparison: when
+    /// `==` would convert `value` some other way, or fail to.
+    pub fn contains(
+        &self,
+        coercion: CoercionPolicy,
+        collation: Collation,
+        value: &Value,
+    ) -> Option<bool> {
+        match (self, value) {
+            (LiteralSet::Texts(texts), Value::String(v)) => {
+                Some(texts.contains(collation.key(v.text()).as_ref()))
+            }
+            (LiteralSet::Numbers(_), Value::Float(v)) if !v.is_finite() => Some(false),
+            (LiteralSet::Numbers(numbers), Value::Int(_) | Value::Float(_) | Value::Decimal(_)) => {
+                Some(numbers.contains(&number_key(value)?))
+            }
+            (LiteralSet::Numbers(_), Value::String(_)) if coercion == CoercionPolicy::None => {
+                Some(false)
+            }
+            (LiteralSet::Numbers(numbers), Value::String(_)) => {
+                let number = ValueArithmetic::to_number(value).ok()?;
+                Some(numbers.contains(&number_key(&number)?))
+            }
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hawk_parser::parse_expr;
+
+    fn set(input: &str) -> Option<LiteralSet> {
+        match parse_expr(input).unwrap().1 {
+            Expr::List(items) => LiteralSet::build(&items, Collation::default()),
+            expr => panic!("{expr:?} is not a list"),
+        }
+    }
+
+    #[test]
+    fn test_literal_set() {
+        let numbers = (0..20).map(|v| format!("{v}.0")).collect::<Vec<_>>();
+        let numbers = set(&format!("[{}, 99999999999]", numbers.join(", "))).unwrap();
+        let contains =
+            |value| numbers.contains(CoercionPolicy::Strict, Collation::default(), &value);
+        assert_eq!(contains(Value::from(3)), Some(true));
+        assert_eq!(contains(Value::from(20)), Some(false));
+        assert_eq!(contains(Value::Float(19.0)), Some(true));
+        assert_eq!(contains(Value::Float(f64::NAN)), Some(false));
+        assert_eq!(contains(Value::Decimal(Decimal::new(700, -2))), Some(true));
+        assert_eq!(contains(Value::from(" 99999999999")), Some(true));
+        assert_eq!(contains(Value::from("abc")), None);
+        assert_eq!(contains(Value::Bool(true)), None);
+        assert_eq!(
+            numbers.contains(
+                CoercionPolicy::None,
+                Collation::default(),
+                &Value::from("1")
+            ),
+            Some(false)
+        );
+
+        let texts = (0..20).map(|v| format!("\"{v}\"")).collect::<Vec<_>>();
+        let texts = set(&format!("[{}]", texts.join(", "))).unwrap();
+        assert_eq!(
+            texts.contains(
+                CoercionPolicy::Strict,
+                Collation::default(),
+                &Value::from("7")
+            ),
+            Some(true)
+        );
+        assert_eq!(
+            texts.contains(
+                CoercionPolicy::Strict,
+                Collation::default(),
+                &Value::from(7)
+            ),
+            None
+        );
+
+        assert!(set("[1, 2, 3]").is_none());
+        let mixed = (0..20).map(|v| v.to_string()).collect::<Vec<_>>();
+        assert!(set(&format!("[{}, \"a\"]", mixed.join(", "))).is_none());
+        assert!(set(&format!("[{}, 1e0]", mixed.join(", "))).is_none());
+    }
+}
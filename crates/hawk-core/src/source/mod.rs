@@ -1,141 +1,2107 @@
 use anyhow::{anyhow, Result};
 use chrono::{DateTime, FixedOffset};
-use hawk_parser::Expr;
+use hawk_parser::{program::Program, template::TemplatePart, Expr, TypeName};
 use ion_rs::{
-    element::{Element, Value},
-    external::bigdecimal::{num_bigint::BigInt, BigDecimal},
-    types::{Decimal, Int, IonType, Str, Struct, Timestamp},
+    element::{Element, Sequence, Value},
+    external::bigdecimal::{num_bigint::BigInt, BigDecimal, FromPrimitive, ToPrimitive, Zero},
+    types::{Decimal, Int, IntAccess, IonType, Str, Struct, Symbol, Timestamp},
     IonData,
 };
-use std::{borrow::Cow, str::FromStr};
+use rand::{rngs::StdRng, SeedableRng};
+use regex::{Captures, Regex};
+use std::{
+    borrow::Cow,
+    cell::{Cell, Ref, RefCell, RefMut},
+    cmp::Ordering,
+    collections::HashMap,
+    marker::PhantomData,
+    rc::Rc,
+    str::FromStr,
+};
+
+use self::collation::Collation;
+use self::functions::{
+    datetime::{epoch_timestamp, format_timestamp, parse_timestamp},
+    FunctionRegistry,
+};
+use self::membership::LiteralSet;
+use crate::template::value_text;
 
+pub mod collation;
 pub mod csv;
+pub mod functions;
+pub mod membership;
+pub mod pattern;
 
 pub trait IonIterator: Iterator<Item = Element> {}
 
-pub fn resolve_var<'a>(item: &'a Struct, expr: &Expr) -> Result<&'a Value> {
-    if let Expr::Variable(variable) = expr {
-        if variable.starts_with('$') {
-            if let Some(field_number) = variable.strip_prefix('$') {
-                let field_number: usize = field_number.parse()?;
-                if field_number >= 1 && field_number <= item.len() {
-                    if let Some((_, element)) = item.fields().nth(field_number - 1) {
-                        return Ok(element.value());
-                    }
+/// The variable that refers to the current record's fields as a whole.
+pub const RECORD_VARIABLE: &str = "input";
+
+/// The awk-style whole record reference; see [`resolve_record`].
+pub const WHOLE_RECORD_VARIABLE: &str = "$0";
+
+/// The groups captured by the last successful `~` match, keyed by group name
+/// or, for unnamed groups, by number (`captures[0]` is the whole match).
+pub const CAPTURES_VARIABLE: &str = "captures";
+
+/// What comparisons do with operands of different types.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub enum CoercionPolicy {
+    /// Convert one operand to the other's type, failing when it cannot be
+    /// converted: `"abc" < 5` is an error.
+    #[default]
+    Strict,
+    /// Convert where possible, and otherwise compare the operands' text:
+    /// `"abc" < 5` compares `"abc"` with `"5"`.
+    Lenient,
+    /// Never convert text. Numbers still compare by value whatever their
+    /// type, so `1 == 1.0` holds; other values of different types are
+    /// unequal and are ordered by type, as Ion orders them.
+    None,
+}
+
+impl FromStr for CoercionPolicy {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "strict" => Ok(CoercionPolicy::Strict),
+            "lenient" => Ok(CoercionPolicy::Lenient),
+            "none" => Ok(CoercionPolicy::None),
+            _ => Err(anyhow!(
+                "unknown coercion policy \"{s}\" (expected strict, lenient or none)"
+            )),
+        }
+    }
+}
+
+impl CoercionPolicy {
+    /// Brings two compared values to a common type as the policy allows.
+    pub fn coerce<'a, 'b>(
+        self,
+        lhs: &'a Value,
+        rhs: &'b Value,
+    ) -> Result<(Cow<'a, Value>, Cow<'b, Value>)> {
+        match self {
+            CoercionPolicy::Strict => ValueImplicitConversion::coerce(lhs, rhs)
+                .map_err(|e| anyhow!("Cannot compare {lhs} with {rhs}: {e}")),
+            CoercionPolicy::Lenient => ValueImplicitConversion::coerce(lhs, rhs).or_else(|_| {
+                let text = |v: &Value| Cow::Owned(Value::String(Str::from(value_text(v))));
+                Ok((text(lhs), text(rhs)))
+            }),
+            CoercionPolicy::None => Ok((Cow::Borrowed(lhs), Cow::Borrowed(rhs))),
+        }
+    }
+}
+
+/// How many patterns taken from record values are kept compiled at once;
+/// the cache is emptied when it fills, so it stays bounded however many
+/// distinct values a run sees.
+const DYNAMIC_REGEX_LIMIT: usize = 256;
+
+/// Settings and state shared by every expression evaluated over a run. Once
+/// a program is prepared, the context borrows it for `'p`.
+#[derive(Debug, Default)]
+pub struct Context<'p> {
+    /// Separator between fields of records read from delimited text. `None`
+    /// for structured sources.
+    pub field_separator: Option<String>,
+    /// How values of different types are compared.
+    pub coercion: CoercionPolicy,
+    /// How strings are compared, unless an operator says otherwise with
+    /// `collate`.
+    pub collation: Collation,
+    /// The functions that `name(args...)` calls can reach.
+    pub functions: FunctionRegistry,
+    /// The program's literal patterns, compiled by [`prepare_program`].
+    regexes: RefCell<HashMap<String, Rc<Regex>>>,
+    dynamic_regexes: RefCell<HashMap<String, Rc<Regex>>>,
+    /// The program's large `in` lists, hashed by [`prepare_program`] and
+    /// keyed by the address of the list; the borrow of the program keeps
+    /// each address naming the same list for as long as the context lives.
+    literal_sets: RefCell<HashMap<*const Expr, LiteralSet>>,
+    /// Invariant, so a context cannot be prepared with an expression that
+    /// does not outlive it.
+    program: PhantomData<Cell<&'p Expr>>,
+    captures: RefCell<Option<Struct>>,
+    line: RefCell<Option<String>>,
+    collate: Cell<Option<Collation>>,
+    rng: RefCell<Option<StdRng>>,
+}
+
+impl<'p> Context<'p> {
+    pub fn new(field_separator: Option<String>) -> Self {
+        Context {
+            field_separator,
+            ..Default::default()
+        }
+    }
+
+    /// Returns the compiled form of `pattern`. The program's literal
+    /// patterns are compiled when it is prepared; others are compiled when
+    /// first used and kept in a bounded cache.
+    pub fn regex(&self, pattern: &str) -> Result<Rc<Regex>> {
+        if let Some(regex) = self.regexes.borrow().get(pattern) {
+            return Ok(regex.clone());
+        }
+        let mut dynamic = self.dynamic_regexes.borrow_mut();
+        if let Some(regex) = dynamic.get(pattern) {
+            return Ok(regex.clone());
+        }
+        let regex = Rc::new(Regex::new(pattern)?);
+        if dynamic.len() >= DYNAMIC_REGEX_LIMIT {
+            dynamic.clear();
+        }
+        dynamic.insert(pattern.to_string(), regex.clone());
+        Ok(regex)
+    }
+
+    /// Compiles a literal pattern of the program, to be kept for the run.
+    fn compile_literal(&self, pattern: &str) -> Result<()> {
+        if !self.regexes.borrow().contains_key(pattern) {
+            let regex = Regex::new(pattern)?;
+            self.regexes
+                .borrow_mut()
+                .insert(pattern.to_string(), Rc::new(regex));
+        }
+        Ok(())
+    }
+
+    /// Returns the hashed form of the `in` list `list`, if it was given one
+    /// when the program was prepared.
+    fn literal_set(&self, list: &Expr) -> Option<Ref<'_, LiteralSet>> {
+        Ref::filter_map(self.literal_sets.borrow(), |sets| {
+            sets.get(&(list as *const Expr))
+        })
+        .ok()
+    }
+
+    /// Sets the text of the current record as it was read, which `$0`
+    /// returns; called before each record read from delimited text.
+    pub fn set_line(&self, line: &str) {
+        let mut current = self.line.borrow_mut();
+        let current = current.get_or_insert_with(String::new);
+        current.clear();
+        current.push_str(line);
+    }
+
+    /// The collation comparisons are made under: the one named by the
+    /// innermost enclosing `collate`, or else [`Context::collation`].
+    pub fn collation(&self) -> Collation {
+        self.collate.get().unwrap_or(self.collation)
+    }
+
+    fn set_captures(&self, regex: &Regex, captures: &Captures) {
+        let mut builder = Element::struct_builder();
+        for (i, name) in regex.capture_names().enumerate() {
+            let name = name.map_or_else(|| i.to_string(), str::to_string);
+            builder = match captures.get(i) {
+                Some(capture) => builder.with_field(name, capture.as_str()),
+                None => builder.with_field(name, Element::null(IonType::String)),
+            };
+        }
+        *self.captures.borrow_mut() = Some(builder.build());
+    }
+
+    /// Forgets the captures of earlier matches; called before each record.
+    pub fn clear_captures(&self) {
+        *self.captures.borrow_mut() = None;
+    }
+
+    /// Seeds the generator behind `rand()`, which is otherwise seeded from
+    /// the operating system.
+    pub fn seed(&self, seed: u64) {
+        *self.rng.borrow_mut() = Some(StdRng::seed_from_u64(seed));
+    }
+
+    pub(crate) fn rng(&self) -> RefMut<'_, StdRng> {
+        RefMut::map(self.rng.borrow_mut(), |rng| {
+            rng.get_or_insert_with(StdRng::from_entropy)
+        })
+    }
+}
+
+/// The value of `$0`: the record's line for delimited text sources, as set
+/// by [`Context::set_line`] or else rebuilt from its fields, or the record
+/// itself for structured sources.
+pub fn resolve_record(ctx: &Context, item: &Struct) -> Value {
+    match &ctx.field_separator {
+        Some(_) if ctx.line.borrow().is_some() => {
+            Value::String(Str::from(ctx.line.borrow().clone().unwrap_or_default()))
+        }
+        Some(separator) => {
+            let fields: Vec<String> = item
+                .fields()
+                .map(|(_, element)| value_text(element.value()))
+                .collect();
+            Value::String(Str::from(fields.join(separator)))
+        }
+        None => Value::Struct(item.clone()),
+    }
+}
+
+fn path_value(mut elements: Vec<&Element>) -> Option<Cow<'_, Value>> {
+    match elements.len() {
+        0 => None,
+        1 => Some(Cow::Borrowed(elements.remove(0).value())),
+        _ => Some(Cow::Owned(Value::List(Sequence::new(elements)))),
+    }
+}
+
+/// Looks up `$0`, `input`, `captures`, `$N` positional fields, field names
+/// and dotted paths through nested structs, or returns `None` when the record
+/// has no such field. A path that fans out across lists resolves to a list of
+/// every value it reaches.
+fn lookup_var<'a>(ctx: &Context, item: &'a Struct, variable: &str) -> Option<Cow<'a, Value>> {
+    if variable == WHOLE_RECORD_VARIABLE {
+        return Some(Cow::Owned(resolve_record(ctx, item)));
+    }
+    if variable == RECORD_VARIABLE {
+        return Some(Cow::Owned(Value::Struct(item.clone())));
+    }
+    if variable == CAPTURES_VARIABLE {
+        let captures = ctx.captures.borrow().clone();
+        let captures = captures.unwrap_or_else(|| Element::struct_builder().build());
+        return Some(Cow::Owned(Value::Struct(captures)));
+    }
+    let subpath = |root: &str| {
+        variable
+            .strip_prefix(root)
+            .and_then(|path| path.strip_prefix('.'))
+    };
+    if let Some(path) = subpath(CAPTURES_VARIABLE) {
+        let captures = ctx.captures.borrow();
+        let value = path_value(resolve_path_elements(captures.as_ref()?, path))?;
+        return Some(Cow::Owned(value.into_owned()));
+    }
+    // `input."first-name"` names a field that is not an identifier.
+    let path = subpath(RECORD_VARIABLE).unwrap_or(variable);
+    path_value(resolve_path_elements(item, path))
+}
+
+/// Resolves a variable as described in [`lookup_var`]. A field the record
+/// does not have, such as `$7` of a short CSV row, is missing and resolves to
+/// null; `is missing` tells the two apart.
+pub fn resolve_var<'a>(ctx: &Context, item: &'a Struct, expr: &Expr) -> Result<Cow<'a, Value>> {
+    match expr {
+        Expr::Variable(variable) => {
+            Ok(lookup_var(ctx, item, variable).unwrap_or(Cow::Owned(Value::Null(IonType::Null))))
+        }
+        _ => Err(anyhow!("No value")),
+    }
+}
+
+fn struct_fields<'a>(item: &'a Struct, segment: &str) -> Vec<&'a Element> {
+    match segment.strip_prefix('$').map(str::parse::<usize>) {
+        Some(Ok(field_number)) if field_number >= 1 => item
+            .fields()
+            .nth(field_number - 1)
+            .map(|(_, element)| element)
+            .into_iter()
+            .collect(),
+        _ => item.get_all(segment).collect(),
+    }
+}
+
+/// Follows a dotted path through nested structs, fanning out across any lists
+/// met along the way, and returns every element the path leads to.
+pub fn resolve_path_elements<'a>(item: &'a Struct, path: &str) -> Vec<&'a Element> {
+    let mut segments = path.split('.');
+    let mut elements = match segments.next() {
+        Some(segment) => struct_fields(item, segment),
+        None => Vec::new(),
+    };
+    for segment in segments {
+        elements = elements
+            .into_iter()
+            .flat_map(|element| match element.value() {
+                Value::Struct(v) => struct_fields(v, segment),
+                Value::List(v) | Value::SExp(v) => v
+                    .elements()
+                    .filter_map(Element::as_struct)
+                    .flat_map(|v| struct_fields(v, segment))
+                    .collect(),
+                _ => Vec::new(),
+            })
+            .collect();
+    }
+    elements
+}
+
+/// Evaluates `path[predicate]`: the elements at `path` (or, for lists, their
+/// members) are each used as the record for `predicate`, and those for which
+/// it holds are returned as a list.
+pub fn resolve_predicate(
+    ctx: &Context,
+    item: &Struct,
+    path: &str,
+    predicate: &Expr,
+) -> Result<Value> {
+    let mut matches = Vec::new();
+    for element in resolve_path_elements(item, path) {
+        let candidates: Vec<&Element> = match element.value() {
+            Value::List(v) | Value::SExp(v) => v.elements().collect(),
+            _ => vec![element],
+        };
+        for candidate in candidates {
+            if let Some(candidate_struct) = candidate.as_struct() {
+                if let Value::Bool(true) = resolve_expr(ctx, candidate_struct, predicate)?.as_ref()
+                {
+                    matches.push(candidate.clone());
                 }
             }
         }
     }
-    Err(anyhow!("No value"))
+    Ok(Value::List(Sequence::new(matches)))
+}
+
+/// Maps a zero-based index, negative counting back from the end, onto a
+/// position within a collection of `len` items.
+fn index_position(index: i64, len: usize) -> Option<usize> {
+    let position = if index < 0 { len as i64 + index } else { index };
+    usize::try_from(position)
+        .ok()
+        .filter(|position| *position < len)
+}
+
+fn index_struct(item: &Struct, index: i64) -> Option<&Value> {
+    let position = index_position(index, item.len())?;
+    item.fields()
+        .nth(position)
+        .map(|(_, element)| element.value())
+}
+
+fn index_value(value: &Value, index: i64) -> Option<&Value> {
+    match value {
+        Value::Struct(v) => index_struct(v, index),
+        Value::List(v) | Value::SExp(v) => {
+            let position = index_position(index, v.len())?;
+            v.get(position).map(|element| element.value())
+        }
+        _ => None,
+    }
+}
+
+fn index_text(text: &str, index: i64) -> Option<Value> {
+    let position = index_position(index, text.chars().count())?;
+    text.chars()
+        .nth(position)
+        .map(|c| Value::String(Str::from(c.to_string())))
+}
+
+fn lookup_index<'a>(
+    ctx: &Context,
+    item: &'a Struct,
+    base: &Expr,
+    index: &Expr,
+) -> Result<Option<Cow<'a, Value>>> {
+    let index = match resolve_expr(ctx, item, index)?.as_ref() {
+        Value::Int(v) => v.as_i64(),
+        Value::String(v) => v.text().parse().ok(),
+        _ => None,
+    }
+    .ok_or_else(|| anyhow!("Index must be an integer"))?;
+
+    let indexed = match base {
+        Expr::Variable(variable) if variable == RECORD_VARIABLE => {
+            index_struct(item, index).map(Cow::Borrowed)
+        }
+        _ => match resolve_expr(ctx, item, base)? {
+            Cow::Borrowed(Value::String(v)) => index_text(v.text(), index).map(Cow::Owned),
+            Cow::Borrowed(value) => index_value(value, index).map(Cow::Borrowed),
+            Cow::Owned(Value::String(v)) => index_text(v.text(), index).map(Cow::Owned),
+            Cow::Owned(value) => index_value(&value, index).cloned().map(Cow::Owned),
+        },
+    };
+    Ok(indexed)
+}
+
+/// Resolves `base[index]`; an index out of range is missing and, like a
+/// missing field, resolves to null.
+pub fn resolve_index<'a>(
+    ctx: &Context,
+    item: &'a Struct,
+    base: &Expr,
+    index: &Expr,
+) -> Result<Cow<'a, Value>> {
+    Ok(lookup_index(ctx, item, base, index)?.unwrap_or(Cow::Owned(Value::Null(IonType::Null))))
+}
+
+/// Evaluates `expr is missing`: whether `expr` names a field or index the
+/// record does not have. Any other expression is never missing.
+pub fn resolve_missing(ctx: &Context, item: &Struct, expr: &Expr) -> Result<bool> {
+    match expr {
+        Expr::Variable(variable) => Ok(lookup_var(ctx, item, variable).is_none()),
+        Expr::Index(base, index) => Ok(lookup_index(ctx, item, base, index)?.is_none()),
+        _ => Ok(false),
+    }
+}
+
+pub fn ion_type(type_name: TypeName) -> IonType {
+    match type_name {
+        TypeName::Null => IonType::Null,
+        TypeName::Bool => IonType::Bool,
+        TypeName::Int => IonType::Int,
+        TypeName::Float => IonType::Float,
+        TypeName::Decimal => IonType::Decimal,
+        TypeName::Timestamp => IonType::Timestamp,
+        TypeName::Symbol => IonType::Symbol,
+        TypeName::String => IonType::String,
+        TypeName::Clob => IonType::Clob,
+        TypeName::Blob => IonType::Blob,
+        TypeName::List => IonType::List,
+        TypeName::SExp => IonType::SExp,
+        TypeName::Struct => IonType::Struct,
+    }
+}
+
+pub fn resolve_timestamp(text: &str) -> Result<Value> {
+    match Element::read_one(text)?.value() {
+        Value::Timestamp(v) => Ok(Value::Timestamp(v.clone())),
+        _ => Err(anyhow!("{text} is not a timestamp")),
+    }
 }
 
 pub struct ValueImplicitConversion {}
 impl ValueImplicitConversion {
-    fn coerce_value(value: &Value, ion_type: IonType) -> Result<Cow<Value>> {
+    /// The exact value of a finite float; every finite binary float has a
+    /// terminating decimal expansion.
+    fn exact_decimal(value: f64) -> Option<BigDecimal> {
+        if !value.is_finite() {
+            return None;
+        }
+        let bits = value.to_bits();
+        let fraction = bits & 0xf_ffff_ffff_ffff;
+        let (mantissa, exponent) = match (bits >> 52) & 0x7ff {
+            0 => (fraction, -1074),
+            exponent => (fraction | 1 << 52, exponent as i64 - 1075),
+        };
+        let mantissa = match bits >> 63 {
+            1 => -BigInt::from(mantissa),
+            _ => BigInt::from(mantissa),
+        };
+        Some(if exponent >= 0 {
+            BigDecimal::new(mantissa << exponent as usize, 0)
+        } else {
+            // m * 2^-k == m * 5^k * 10^-k
+            BigDecimal::new(mantissa * BigInt::from(5).pow(-exponent as u32), -exponent)
+        })
+    }
+
+    fn coerce_value(value: &Value, ion_type: IonType) -> Result<Cow<'_, Value>> {
         match (value, ion_type) {
             (Value::String(v), IonType::Bool) => Ok(Cow::Owned(Value::Bool(v.text().parse()?))),
-            (Value::String(v), IonType::Int) => {
-                Ok(Cow::Owned(Value::Int(Int::BigInt(v.text().parse()?))))
-            }
+            (Value::String(v), IonType::Int) => match v.text().parse::<i64>() {
+                Ok(v) => Ok(Cow::Owned(Value::Int(Int::I64(v)))),
+                Err(_) => Ok(Cow::Owned(Value::Int(Int::BigInt(v.text().parse()?)))),
+            },
             (Value::String(v), IonType::Float) => Ok(Cow::Owned(Value::Float(v.text().parse()?))),
             (Value::String(v), IonType::Decimal) => {
                 let decimal = BigDecimal::from_str(v.text())?;
                 Ok(Cow::Owned(Value::Decimal(Decimal::from(decimal))))
             }
+            (Value::String(v), IonType::Symbol) => {
+                Ok(Cow::Owned(Value::Symbol(Symbol::owned(v.text()))))
+            }
+            (Value::Null(_), IonType::Null) => Ok(Cow::Owned(Value::Null(IonType::Null))),
             (Value::String(v), IonType::Timestamp) => {
-                let datetime: DateTime<FixedOffset> = v.text().parse()?;
-                Ok(Cow::Owned(Value::Timestamp(Timestamp::from(datetime))))
+                match v.text().trim().parse::<DateTime<FixedOffset>>() {
+                    Ok(datetime) => Ok(Cow::Owned(Value::Timestamp(Timestamp::from(datetime)))),
+                    Err(_) => Ok(Cow::Owned(resolve_timestamp(v.text().trim())?)),
+                }
+            }
+            (Value::Int(_) | Value::Decimal(_), IonType::Float) => {
+                Ok(Cow::Owned(Value::Float(ValueArithmetic::to_f64(value)?)))
             }
-            (Value::Int(Int::I64(v)), IonType::Float) => Ok(Cow::Owned(Value::Float(*v as f64))),
-            (Value::Int(Int::I64(v)), IonType::Decimal) => {
-                let decimal = BigDecimal::new(BigInt::from(*v), 0);
+            (Value::Int(v), IonType::Decimal) => {
+                let decimal = BigDecimal::new(ValueArithmetic::big_int(v), 0);
                 Ok(Cow::Owned(Value::Decimal(Decimal::from(decimal))))
             }
-            (Value::Int(Int::BigInt(v)), IonType::Decimal) => {
-                let decimal = BigDecimal::new(v.clone(), 0);
+            (Value::Float(v), IonType::Decimal) => {
+                let decimal = Self::exact_decimal(*v)
+                    .ok_or_else(|| anyhow!("{v} cannot be represented as a decimal"))?;
                 Ok(Cow::Owned(Value::Decimal(Decimal::from(decimal))))
             }
             _ => Ok(Cow::Borrowed(value)),
         }
     }
 
+    fn convert(value: Cow<'_, Value>, ion_type: IonType) -> Result<Cow<'_, Value>> {
+        match value {
+            Cow::Borrowed(v) => Self::coerce_value(v, ion_type),
+            Cow::Owned(v) => Ok(Cow::Owned(Self::coerce_value(&v, ion_type)?.into_owned())),
+        }
+    }
+
+    /// The type two numbers are brought to before they are combined. Ints
+    /// meeting Decimals become Decimals. A Float meeting either becomes a
+    /// Float for arithmetic, but when `exact`, as for comparisons, the Float
+    /// is read as the Decimal it represents exactly, unless it is infinite
+    /// or NaN.
+    fn numeric_type(lhs: &Value, rhs: &Value, exact: bool) -> Option<IonType> {
+        let is_number =
+            |v: &Value| matches!(v, Value::Int(_) | Value::Float(_) | Value::Decimal(_));
+        let is_finite = |v: &Value| !matches!(v, Value::Float(f) if !f.is_finite());
+        if !is_number(lhs) || !is_number(rhs) {
+            return None;
+        }
+        Some(match (lhs.ion_type(), rhs.ion_type()) {
+            (lhs, rhs) if lhs == rhs => lhs,
+            (IonType::Float, _) | (_, IonType::Float)
+                if !exact || !is_finite(lhs) || !is_finite(rhs) =>
+            {
+                IonType::Float
+            }
+            _ => IonType::Decimal,
+        })
+    }
+
+    fn promote<'a, 'b>(
+        lhs: Cow<'a, Value>,
+        rhs: Cow<'b, Value>,
+        exact: bool,
+    ) -> Result<(Cow<'a, Value>, Cow<'b, Value>)> {
+        match Self::numeric_type(lhs.as_ref(), rhs.as_ref(), exact) {
+            Some(ion_type) => Ok((Self::convert(lhs, ion_type)?, Self::convert(rhs, ion_type)?)),
+            None => Ok((lhs, rhs)),
+        }
+    }
+
+    /// Brings two compared values to a common type. A string compared with
+    /// an Int or Decimal is read as a number; numbers then meet as described
+    /// in [`Self::numeric_type`].
     fn coerce<'a, 'b>(lhs: &'a Value, rhs: &'b Value) -> Result<(Cow<'a, Value>, Cow<'b, Value>)> {
-        if lhs.ion_type() != rhs.ion_type() {
-            Ok((
+        if lhs.ion_type() == rhs.ion_type() {
+            return Ok((Cow::Borrowed(lhs), Cow::Borrowed(rhs)));
+        }
+        let exact_number = |v: &Value| matches!(v, Value::Int(_) | Value::Decimal(_));
+        match (lhs, rhs) {
+            (Value::String(_), rhs) if exact_number(rhs) => {
+                Self::promote(ValueArithmetic::to_number(lhs)?, Cow::Borrowed(rhs), true)
+            }
+            (lhs, Value::String(_)) if exact_number(lhs) => {
+                Self::promote(Cow::Borrowed(lhs), ValueArithmetic::to_number(rhs)?, true)
+            }
+            _ if Self::numeric_type(lhs, rhs, true).is_some() => {
+                Self::promote(Cow::Borrowed(lhs), Cow::Borrowed(rhs), true)
+            }
+            _ => Ok((
                 ValueImplicitConversion::coerce_value(lhs, rhs.ion_type())?,
                 ValueImplicitConversion::coerce_value(rhs, lhs.ion_type())?,
-            ))
-        } else {
-            Ok((Cow::Borrowed(lhs), Cow::Borrowed(rhs)))
+            )),
         }
     }
 }
 
-pub fn resolve_cond(item: &Struct, expr: &Expr) -> Result<Value> {
+/// Equality and ordering of values. Numbers compare by their mathematical
+/// value whatever their Ion types, and timestamps by the instant they name;
+/// anything else falls back to Ion's ordering.
+pub struct ValueComparison {}
+impl ValueComparison {
+    pub fn compare(lhs: &Value, rhs: &Value) -> Option<Ordering> {
+        if ValueImplicitConversion::numeric_type(lhs, rhs, true).is_some() {
+            let (lhs, rhs) =
+                ValueImplicitConversion::promote(Cow::Borrowed(lhs), Cow::Borrowed(rhs), true)
+                    .ok()?;
+            return match (lhs.as_ref(), rhs.as_ref()) {
+                (Value::Int(lhs), Value::Int(rhs)) => Some(lhs.cmp(rhs)),
+                (Value::Float(lhs), Value::Float(rhs)) => lhs.partial_cmp(rhs),
+                (Value::Decimal(lhs), Value::Decimal(rhs)) => Some(lhs.cmp(rhs)),
+                _ => None,
+            };
+        }
+        match (lhs, rhs) {
+            (Value::Timestamp(lhs), Value::Timestamp(rhs)) => Some(lhs.cmp(rhs)),
+            _ => Some(IonData::from(lhs).cmp(&IonData::from(rhs))),
+        }
+    }
+
+    pub fn equal(lhs: &Value, rhs: &Value) -> bool {
+        match (lhs, rhs) {
+            (
+                Value::Int(_) | Value::Float(_) | Value::Decimal(_) | Value::Timestamp(_),
+                Value::Int(_) | Value::Float(_) | Value::Decimal(_) | Value::Timestamp(_),
+            ) => Self::compare(lhs, rhs) == Some(Ordering::Equal),
+            _ => lhs == rhs,
+        }
+    }
+}
+
+/// Conversions asked for with `type(expr)` or `expr as type`. Unlike
+/// [`ValueImplicitConversion`], these always convert to the named type or fail.
+pub struct ValueExplicitConversion {}
+impl ValueExplicitConversion {
+    fn to_int(value: &Value) -> Result<Value> {
+        match value {
+            Value::Int(_) => Ok(value.clone()),
+            Value::Bool(v) => Ok(Value::Int(Int::I64(i64::from(*v)))),
+            Value::Float(v) => BigInt::from_f64(v.trunc())
+                .map(ValueArithmetic::from_big_int)
+                .ok_or_else(|| anyhow!("Cannot cast {v} to int")),
+            Value::Decimal(v) => {
+                let truncated = ValueArithmetic::big_decimal(v).with_scale(0);
+                Ok(ValueArithmetic::from_big_int(
+                    truncated.as_bigint_and_exponent().0,
+                ))
+            }
+            Value::String(_) => Self::to_int(ValueArithmetic::to_number(value)?.as_ref()),
+            _ => Err(anyhow!("Cannot cast {value} to int")),
+        }
+    }
+
+    fn to_float(value: &Value) -> Result<Value> {
+        match value {
+            Value::String(v) => match v.text().trim().parse() {
+                Ok(v) => Ok(Value::Float(v)),
+                Err(_) => Ok(Value::Float(ValueArithmetic::to_f64(value)?)),
+            },
+            Value::Int(_) | Value::Float(_) | Value::Decimal(_) => {
+                Ok(Value::Float(ValueArithmetic::to_f64(value)?))
+            }
+            _ => Err(anyhow!("Cannot cast {value} to float")),
+        }
+    }
+
+    fn to_decimal(value: &Value) -> Result<Value> {
+        let decimal = match value {
+            Value::Decimal(_) => return Ok(value.clone()),
+            Value::Int(v) => BigDecimal::new(ValueArithmetic::big_int(v), 0),
+            Value::Float(v) if v.is_finite() => BigDecimal::from_str(&v.to_string())?,
+            Value::String(v) => BigDecimal::from_str(v.text().trim())?,
+            _ => return Err(anyhow!("Cannot cast {value} to decimal")),
+        };
+        Ok(Value::Decimal(Decimal::from(decimal)))
+    }
+
+    fn to_bool(value: &Value) -> Result<Value> {
+        match value {
+            Value::Bool(_) => Ok(value.clone()),
+            Value::String(v) => match v.text().trim().to_lowercase().as_str() {
+                "true" | "1" => Ok(Value::Bool(true)),
+                "false" | "0" => Ok(Value::Bool(false)),
+                _ => Err(anyhow!("Cannot cast {value} to bool")),
+            },
+            Value::Int(_) | Value::Float(_) | Value::Decimal(_) => {
+                Ok(Value::Bool(ValueArithmetic::to_f64(value)? != 0.0))
+            }
+            _ => Err(anyhow!("Cannot cast {value} to bool")),
+        }
+    }
+
+    /// Strings are read with `format` when one is given, and otherwise as
+    /// RFC 3339 or Ion timestamps; integers are seconds since the epoch.
+    fn to_timestamp(value: &Value, format: Option<&str>) -> Result<Value> {
+        match (value, format) {
+            (Value::Timestamp(_), None) => Ok(value.clone()),
+            (Value::String(v), Some(format)) => parse_timestamp(v.text(), format),
+            (Value::String(_), None) => {
+                ValueImplicitConversion::coerce_value(value, IonType::Timestamp)
+                    .map(Cow::into_owned)
+            }
+            (Value::Int(Int::I64(v)), None) => epoch_timestamp(*v),
+            _ => Err(anyhow!("Cannot cast {value} to timestamp")),
+        }
+    }
+
+    /// Timestamps are written with `format` when one is given.
+    fn to_text(value: &Value, format: Option<&str>) -> Result<String> {
+        match (value, format) {
+            (Value::Timestamp(_), Some(format)) => format_timestamp(value, format),
+            (_, None) => Ok(value_text(value)),
+            _ => Err(anyhow!("Only timestamps can be written with a format")),
+        }
+    }
+
+    pub fn cast(value: &Value, type_name: TypeName, format: Option<&str>) -> Result<Value> {
+        if let Value::Null(_) = value {
+            return Ok(Value::Null(ion_type(type_name)));
+        }
+        if format.is_some() && !matches!(type_name, TypeName::Timestamp | TypeName::String) {
+            return Err(anyhow!("Only timestamp and string casts take a format"));
+        }
+        match type_name {
+            TypeName::Int => Self::to_int(value),
+            TypeName::Float => Self::to_float(value),
+            TypeName::Decimal => Self::to_decimal(value),
+            TypeName::Bool => Self::to_bool(value),
+            TypeName::Timestamp => Self::to_timestamp(value, format),
+            TypeName::String => Ok(Value::String(Str::from(Self::to_text(value, format)?))),
+            TypeName::Symbol => Ok(Value::Symbol(Symbol::owned(Self::to_text(value, format)?))),
+            _ if value.ion_type() == ion_type(type_name) => Ok(value.clone()),
+            _ => Err(anyhow!("Cannot cast {value} to {:?}", ion_type(type_name))),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+enum ArithmeticOp {
+    Add,
+    Subtract,
+    Multiply,
+    /// Integers divide to an integer, truncating toward zero as in Rust or
+    /// C: `7 / 2` is `3` and `-7 / 2` is `-3`. A decimal or float operand
+    /// keeps the fraction, so `7.0 / 2` is `3.5`.
+    Divide,
+    Modulo,
+}
+
+/// Arithmetic over numeric values. Strings (e.g. CSV fields) are read as
+/// numbers first, then both operands are brought to a common type by
+/// [`ValueImplicitConversion`]. Integer division truncates.
+pub struct ValueArithmetic {}
+impl ValueArithmetic {
+    fn to_number(value: &Value) -> Result<Cow<'_, Value>> {
+        match value {
+            Value::Int(_) | Value::Float(_) | Value::Decimal(_) => Ok(Cow::Borrowed(value)),
+            Value::String(v) => {
+                let text = v.text().trim();
+                if let Ok(v) = text.parse::<i64>() {
+                    Ok(Cow::Owned(Value::Int(Int::I64(v))))
+                } else if let Ok(v) = BigInt::from_str(text) {
+                    Ok(Cow::Owned(Value::Int(Int::BigInt(v))))
+                } else if let Ok(v) = BigDecimal::from_str(text) {
+                    Ok(Cow::Owned(Value::Decimal(Decimal::from(v))))
+                } else {
+                    Err(anyhow!("\"{text}\" is not a number"))
+                }
+            }
+            _ => Err(anyhow!("{value} is not a number")),
+        }
+    }
+
+    fn to_f64(value: &Value) -> Result<f64> {
+        match Self::to_number(value)?.as_ref() {
+            Value::Int(Int::I64(v)) => Ok(*v as f64),
+            Value::Int(Int::BigInt(v)) => v.to_f64().ok_or_else(|| anyhow!("{v} is out of range")),
+            Value::Float(v) => Ok(*v),
+            Value::Decimal(v) => Self::big_decimal(v)
+                .to_f64()
+                .ok_or_else(|| anyhow!("{v} is out of range")),
+            _ => unreachable!(),
+        }
+    }
+
+    /// Wraps `value` as an `Int`, using `Int::I64` whenever it fits.
+    fn from_big_int(value: BigInt) -> Value {
+        match value.to_i64() {
+            Some(v) => Value::Int(Int::I64(v)),
+            None => Value::Int(Int::BigInt(value)),
+        }
+    }
+
+    fn big_int(value: &Int) -> BigInt {
+        match value {
+            Int::I64(v) => BigInt::from(*v),
+            Int::BigInt(v) => v.clone(),
+        }
+    }
+
+    fn big_decimal(value: &Decimal) -> BigDecimal {
+        // Negative zero is the only Decimal that BigDecimal cannot represent.
+        BigDecimal::try_from(value.clone()).unwrap_or_else(|_| BigDecimal::zero())
+    }
+
+    fn apply_int(op: ArithmeticOp, lhs: &Int, rhs: &Int) -> Result<Value> {
+        if let (Int::I64(lhs), Int::I64(rhs)) = (lhs, rhs) {
+            let result = match op {
+                ArithmeticOp::Add => lhs.checked_add(*rhs),
+                ArithmeticOp::Subtract => lhs.checked_sub(*rhs),
+                ArithmeticOp::Multiply => lhs.checked_mul(*rhs),
+                ArithmeticOp::Divide | ArithmeticOp::Modulo if *rhs == 0 => {
+                    return Err(anyhow!("Division by zero"))
+                }
+                ArithmeticOp::Divide => lhs.checked_div(*rhs),
+                ArithmeticOp::Modulo => lhs.checked_rem(*rhs),
+            };
+            if let Some(result) = result {
+                return Ok(Value::Int(Int::I64(result)));
+            }
+        }
+        let (lhs, rhs) = (Self::big_int(lhs), Self::big_int(rhs));
+        let result = match op {
+            ArithmeticOp::Add => lhs + rhs,
+            ArithmeticOp::Subtract => lhs - rhs,
+            ArithmeticOp::Multiply => lhs * rhs,
+            ArithmeticOp::Divide | ArithmeticOp::Modulo if rhs.is_zero() => {
+                return Err(anyhow!("Division by zero"))
+            }
+            ArithmeticOp::Divide => lhs / rhs,
+            ArithmeticOp::Modulo => lhs % rhs,
+        };
+        Ok(Value::Int(Int::BigInt(result)))
+    }
+
+    fn apply_decimal(op: ArithmeticOp, lhs: &Decimal, rhs: &Decimal) -> Result<Value> {
+        let (lhs, rhs) = (Self::big_decimal(lhs), Self::big_decimal(rhs));
+        let result = match op {
+            ArithmeticOp::Add => lhs + rhs,
+            ArithmeticOp::Subtract => lhs - rhs,
+            ArithmeticOp::Multiply => lhs * rhs,
+            ArithmeticOp::Divide | ArithmeticOp::Modulo if rhs.is_zero() => {
+                return Err(anyhow!("Division by zero"))
+            }
+            ArithmeticOp::Divide => lhs / rhs,
+            ArithmeticOp::Modulo => lhs % rhs,
+        };
+        Ok(Value::Decimal(Decimal::from(result)))
+    }
+
+    fn apply(op: ArithmeticOp, lhs: &Value, rhs: &Value) -> Result<Value> {
+        let (lhs, rhs) = (Self::to_number(lhs)?, Self::to_number(rhs)?);
+        let (lhs, rhs) = ValueImplicitConversion::promote(lhs, rhs, false)?;
+        match (lhs.as_ref(), rhs.as_ref()) {
+            (Value::Int(lhs), Value::Int(rhs)) => Self::apply_int(op, lhs, rhs),
+            (Value::Decimal(lhs), Value::Decimal(rhs)) => Self::apply_decimal(op, lhs, rhs),
+            (Value::Float(lhs), Value::Float(rhs)) => Ok(Value::Float(match op {
+                ArithmeticOp::Add => lhs + rhs,
+                ArithmeticOp::Subtract => lhs - rhs,
+                ArithmeticOp::Multiply => lhs * rhs,
+                ArithmeticOp::Divide => lhs / rhs,
+                ArithmeticOp::Modulo => lhs % rhs,
+            })),
+            (lhs, rhs) => Err(anyhow!("Cannot combine {lhs} and {rhs} arithmetically")),
+        }
+    }
+
+    fn negate(value: &Value) -> Result<Value> {
+        match Self::to_number(value)?.as_ref() {
+            Value::Int(Int::I64(v)) => Ok(match v.checked_neg() {
+                Some(v) => Value::Int(Int::I64(v)),
+                None => Value::Int(Int::BigInt(-BigInt::from(*v))),
+            }),
+            Value::Int(Int::BigInt(v)) => Ok(Value::Int(Int::BigInt(-v))),
+            Value::Float(v) => Ok(Value::Float(-v)),
+            Value::Decimal(v) => Ok(Value::Decimal(Decimal::from(-Self::big_decimal(v)))),
+            _ => unreachable!(),
+        }
+    }
+}
+
+pub fn resolve_arithmetic(ctx: &Context, item: &Struct, expr: &Expr) -> Result<Value> {
+    let (op, lhs, rhs) = match expr {
+        Expr::Negate(v) => {
+            return match resolve_expr(ctx, item, v)?.as_ref() {
+                Value::Null(_) => Ok(Value::Null(IonType::Null)),
+                value => ValueArithmetic::negate(value),
+            }
+        }
+        Expr::Add(lhs, rhs) => (ArithmeticOp::Add, lhs, rhs),
+        Expr::Subtract(lhs, rhs) => (ArithmeticOp::Subtract, lhs, rhs),
+        Expr::Multiply(lhs, rhs) => (ArithmeticOp::Multiply, lhs, rhs),
+        Expr::Divide(lhs, rhs) => (ArithmeticOp::Divide, lhs, rhs),
+        Expr::Modulo(lhs, rhs) => (ArithmeticOp::Modulo, lhs, rhs),
+        _ => return Err(anyhow!("No value")),
+    };
+    let (lhs, rhs) = (resolve_expr(ctx, item, lhs)?, resolve_expr(ctx, item, rhs)?);
+    match (lhs.as_ref(), rhs.as_ref()) {
+        (Value::Null(_), _) | (_, Value::Null(_)) => Ok(Value::Null(IonType::Null)),
+        (lhs, rhs) => ValueArithmetic::apply(op, lhs, rhs),
+    }
+}
+
+/// Evaluates `text ~ pattern`. The pattern is either a regex literal or any
+/// expression whose text is used as the pattern; on a match its groups
+/// become the value of `captures`. Matching null text is unknown (`None`).
+pub fn resolve_match(
+    ctx: &Context,
+    item: &Struct,
+    text: &Expr,
+    pattern: &Expr,
+) -> Result<Option<bool>> {
+    let text = match resolve_expr(ctx, item, text)?.as_ref() {
+        Value::Null(_) => return Ok(None),
+        text => value_text(text),
+    };
+    let regex = match pattern {
+        Expr::Regex(pattern) => ctx.regex(pattern)?,
+        _ => ctx.regex(&value_text(resolve_expr(ctx, item, pattern)?.as_ref()))?,
+    };
+    match regex.captures(&text) {
+        Some(captures) => {
+            ctx.set_captures(&regex, &captures);
+            Ok(Some(true))
+        }
+        None => Ok(Some(false)),
+    }
+}
+
+/// Evaluates `text like pattern`, or `ilike` when `case_insensitive`. The
+/// pattern is translated to a regex, so it is compiled once like any other.
+/// Null text or a null pattern is unknown (`None`).
+pub fn resolve_like(
+    ctx: &Context,
+    item: &Struct,
+    text: &Expr,
+    pattern: &Expr,
+    case_insensitive: bool,
+) -> Result<Option<bool>> {
+    let (text, pattern) = (
+        resolve_expr(ctx, item, text)?,
+        resolve_expr(ctx, item, pattern)?,
+    );
+    match (text.as_ref(), pattern.as_ref()) {
+        (Value::Null(_), _) | (_, Value::Null(_)) => Ok(None),
+        (text, pattern) => {
+            let regex = ctx.regex(&pattern::like_regex(&value_text(pattern), case_insensitive))?;
+            Ok(Some(regex.is_match(&value_text(text))))
+        }
+    }
+}
+
+pub fn resolve_cast(
+    ctx: &Context,
+    item: &Struct,
+    expr: &Expr,
+    type_name: TypeName,
+    format: Option<&Expr>,
+) -> Result<Value> {
+    let value = resolve_expr(ctx, item, expr)?;
+    let format = match format {
+        Some(format) => Some(value_text(resolve_expr(ctx, item, format)?.as_ref())),
+        None => None,
+    };
+    ValueExplicitConversion::cast(value.as_ref(), type_name, format.as_deref())
+}
+
+pub fn resolve_call(ctx: &Context, item: &Struct, name: &str, args: &[Expr]) -> Result<Value> {
+    // A regex literal passed to a function stands for its pattern rather
+    // than for a match against the record.
+    let args = args
+        .iter()
+        .map(|arg| match arg {
+            Expr::Regex(pattern) => Ok(Value::String(Str::from(pattern.as_str()))),
+            _ => resolve_expr(ctx, item, arg).map(Cow::into_owned),
+        })
+        .collect::<Result<Vec<_>>>()?;
+    ctx.functions.call(ctx, name, args)
+}
+
+/// The truth value of a condition's operand: `None` for null, which stands
+/// for unknown.
+fn truth(value: &Value) -> Result<Option<bool>> {
+    match value {
+        Value::Bool(v) => Ok(Some(*v)),
+        Value::Null(_) => Ok(None),
+        _ => Err(anyhow!("Error value!")),
+    }
+}
+
+/// The value of a condition: a bool, or `null.bool` when it is unknown.
+fn logical(truth: Option<bool>) -> Value {
+    match truth {
+        Some(v) => Value::Bool(v),
+        None => Value::Null(IonType::Bool),
+    }
+}
+
+/// Evaluates a comparison. As in SQL, comparing anything with null is
+/// unknown; use `is null` to test for it.
+fn compare_values(
+    ctx: &Context,
+    lhs: &Value,
+    rhs: &Value,
+    test: impl Fn(&Value, &Value) -> bool,
+) -> Result<Option<bool>> {
+    if matches!(lhs, Value::Null(_)) || matches!(rhs, Value::Null(_)) {
+        return Ok(None);
+    }
+    let (lhs, rhs) = ctx.coercion.coerce(lhs, rhs)?;
+    Ok(Some(test(&lhs, &rhs)))
+}
+
+fn resolve_comparison(
+    ctx: &Context,
+    item: &Struct,
+    lhs: &Expr,
+    rhs: &Expr,
+    test: impl Fn(&Value, &Value) -> bool,
+) -> Result<Value> {
+    let (lhs, rhs) = (resolve_expr(ctx, item, lhs)?, resolve_expr(ctx, item, rhs)?);
+    Ok(logical(compare_values(
+        ctx,
+        lhs.as_ref(),
+        rhs.as_ref(),
+        test,
+    )?))
+}
+
+/// Evaluates `value in list`, comparing `value` with each member as `==`
+/// does. As in SQL it is unknown, rather than false, when `value` is null or
+/// when nothing matches but some member is null.
+pub fn resolve_in(ctx: &Context, item: &Struct, value: &Expr, list: &Expr) -> Result<Value> {
+    let value = resolve_expr(ctx, item, value)?;
+    if matches!(value.as_ref(), Value::Null(_)) {
+        return Ok(logical(None));
+    }
+    let members: Vec<Cow<Value>> = match list {
+        Expr::List(items) => {
+            if let Some(set) = ctx.literal_set(list) {
+                if let Some(found) = set.contains(ctx.coercion, ctx.collation(), value.as_ref()) {
+                    return Ok(Value::Bool(found));
+                }
+            }
+            items
+                .iter()
+                .map(|item_expr| resolve_expr(ctx, item, item_expr))
+                .collect::<Result<_>>()?
+        }
+        _ => match resolve_expr(ctx, item, list)?.as_ref() {
+            Value::List(v) | Value::SExp(v) => v
+                .elements()
+                .map(|e| Cow::Owned(e.value().clone()))
+                .collect(),
+            Value::Null(_) => return Ok(logical(None)),
+            list => return Err(anyhow!("`in` needs a list, not {list}")),
+        },
+    };
+    let mut found = Some(false);
+    for member in &members {
+        match compare_values(ctx, value.as_ref(), member, |lhs, rhs| {
+            ctx.collation().equal(lhs, rhs)
+        })? {
+            Some(true) => return Ok(Value::Bool(true)),
+            Some(false) => {}
+            None => found = None,
+        }
+    }
+    Ok(logical(found))
+}
+
+/// Evaluates `value between low and high`, which is `value >= low && value
+/// <= high` with `value` evaluated once.
+pub fn resolve_between(
+    ctx: &Context,
+    item: &Struct,
+    value: &Expr,
+    low: &Expr,
+    high: &Expr,
+) -> Result<Value> {
+    let value = resolve_expr(ctx, item, value)?;
+    let within = |bound: &Expr, outside: Ordering| {
+        let bound = resolve_expr(ctx, item, bound)?;
+        compare_values(ctx, value.as_ref(), bound.as_ref(), |lhs, rhs| {
+            ctx.collation()
+                .compare(lhs, rhs)
+                .is_some_and(|order| order != outside)
+        })
+    };
+    Ok(logical(
+        match (
+            within(low, Ordering::Less)?,
+            within(high, Ordering::Greater)?,
+        ) {
+            (Some(false), _) | (_, Some(false)) => Some(false),
+            (Some(true), Some(true)) => Some(true),
+            _ => None,
+        },
+    ))
+}
+
+/// Evaluates comparisons and logical operators with SQL's three-valued
+/// logic: unknown (null) is neither true nor false, `!` leaves it unknown,
+/// and `&&` and `||` are unknown unless the known operand decides them.
+pub fn resolve_cond(ctx: &Context, item: &Struct, expr: &Expr) -> Result<Value> {
+    let collation = ctx.collation();
+    let ordered = |expected: &'static [Ordering]| {
+        move |lhs: &Value, rhs: &Value| {
+            collation
+                .compare(lhs, rhs)
+                .is_some_and(|order| expected.contains(&order))
+        }
+    };
     match expr {
         Expr::Equal(lhs, rhs) => {
-            let (lhs, rhs) = (resolve_expr(item, lhs)?, resolve_expr(item, rhs)?);
-            let (lhs, rhs) = ValueImplicitConversion::coerce(lhs.as_ref(), rhs.as_ref())?;
-            Ok(Value::Bool(lhs == rhs))
+            resolve_comparison(ctx, item, lhs, rhs, |lhs, rhs| collation.equal(lhs, rhs))
         }
         Expr::NotEqual(lhs, rhs) => {
-            let (lhs, rhs) = (resolve_expr(item, lhs)?, resolve_expr(item, rhs)?);
-            let (lhs, rhs) = ValueImplicitConversion::coerce(lhs.as_ref(), rhs.as_ref())?;
-            Ok(Value::Bool(lhs != rhs))
+            resolve_comparison(ctx, item, lhs, rhs, |lhs, rhs| !collation.equal(lhs, rhs))
         }
         Expr::LessThan(lhs, rhs) => {
-            let (lhs, rhs) = (resolve_expr(item, lhs)?, resolve_expr(item, rhs)?);
-            let (lhs, rhs) = ValueImplicitConversion::coerce(lhs.as_ref(), rhs.as_ref())?;
-            Ok(Value::from(
-                IonData::from(lhs.as_ref()) < IonData::from(rhs.as_ref()),
-            ))
-        }
-        Expr::LessThanOrEqual(lhs, rhs) => {
-            let (lhs, rhs) = (resolve_expr(item, lhs)?, resolve_expr(item, rhs)?);
-            let (lhs, rhs) = ValueImplicitConversion::coerce(lhs.as_ref(), rhs.as_ref())?;
-            Ok(Value::from(
-                IonData::from(lhs.as_ref()) <= IonData::from(rhs.as_ref()),
-            ))
+            resolve_comparison(ctx, item, lhs, rhs, ordered(&[Ordering::Less]))
         }
+        Expr::LessThanOrEqual(lhs, rhs) => resolve_comparison(
+            ctx,
+            item,
+            lhs,
+            rhs,
+            ordered(&[Ordering::Less, Ordering::Equal]),
+        ),
         Expr::GreaterThan(lhs, rhs) => {
-            let (lhs, rhs) = (resolve_expr(item, lhs)?, resolve_expr(item, rhs)?);
-            let (lhs, rhs) = ValueImplicitConversion::coerce(lhs.as_ref(), rhs.as_ref())?;
-            Ok(Value::from(
-                IonData::from(lhs.as_ref()) > IonData::from(rhs.as_ref()),
-            ))
-        }
-        Expr::GreaterThanOrEqual(lhs, rhs) => {
-            let (lhs, rhs) = (resolve_expr(item, lhs)?, resolve_expr(item, rhs)?);
-            let (lhs, rhs) = ValueImplicitConversion::coerce(lhs.as_ref(), rhs.as_ref())?;
-            Ok(Value::from(
-                IonData::from(lhs.as_ref()) >= IonData::from(rhs.as_ref()),
-            ))
-        }
-        Expr::And(lhs, rhs) => {
-            let lhs = resolve_expr(item, lhs)?;
-            let rhs = resolve_expr(item, rhs)?;
-            match (lhs.as_ref(), rhs.as_ref()) {
-                (Value::Bool(lhs), Value::Bool(rhs)) => Ok(Value::Bool(*lhs && *rhs)),
-                _ => Err(anyhow!("Error value!")),
-            }
-        }
-        Expr::Or(lhs, rhs) => {
-            let lhs = resolve_expr(item, lhs)?;
-            let rhs = resolve_expr(item, rhs)?;
-            match (lhs.as_ref(), rhs.as_ref()) {
-                (Value::Bool(lhs), Value::Bool(rhs)) => Ok(Value::Bool(*lhs || *rhs)),
-                _ => Err(anyhow!("Error value!")),
-            }
+            resolve_comparison(ctx, item, lhs, rhs, ordered(&[Ordering::Greater]))
+        }
+        Expr::GreaterThanOrEqual(lhs, rhs) => resolve_comparison(
+            ctx,
+            item,
+            lhs,
+            rhs,
+            ordered(&[Ordering::Greater, Ordering::Equal]),
+        ),
+        // A bare regex matches against the whole record, as in awk.
+        Expr::Regex(_) => {
+            let record = Expr::Variable(WHOLE_RECORD_VARIABLE.to_string());
+            Ok(logical(resolve_match(ctx, item, &record, expr)?))
         }
+        Expr::Match(text, pattern) => Ok(logical(resolve_match(ctx, item, text, pattern)?)),
+        Expr::NotMatch(text, pattern) => Ok(logical(
+            resolve_match(ctx, item, text, pattern)?.map(|matched| !matched),
+        )),
+        Expr::IsNull(v) => Ok(Value::Bool(matches!(
+            resolve_expr(ctx, item, v)?.as_ref(),
+            Value::Null(_)
+        ))),
+        Expr::IsMissing(v) => Ok(Value::Bool(resolve_missing(ctx, item, v)?)),
+        Expr::Like(text, pattern) => Ok(logical(resolve_like(ctx, item, text, pattern, false)?)),
+        Expr::ILike(text, pattern) => Ok(logical(resolve_like(ctx, item, text, pattern, true)?)),
+        Expr::In(value, list) => resolve_in(ctx, item, value, list),
+        Expr::Between(value, low, high) => resolve_between(ctx, item, value, low, high),
+        Expr::Not(v) => Ok(logical(
+            truth(resolve_expr(ctx, item, v)?.as_ref())?.map(|v| !v),
+        )),
+        // The right-hand side is only evaluated when the left does not already
+        // decide the result, so guards like `$5 != "" && $5 > 10` are safe.
+        Expr::And(lhs, rhs) => match truth(resolve_expr(ctx, item, lhs)?.as_ref())? {
+            Some(false) => Ok(Value::Bool(false)),
+            lhs => match (lhs, truth(resolve_expr(ctx, item, rhs)?.as_ref())?) {
+                (_, Some(false)) => Ok(Value::Bool(false)),
+                (Some(true), Some(true)) => Ok(Value::Bool(true)),
+                _ => Ok(logical(None)),
+            },
+        },
+        Expr::Or(lhs, rhs) => match truth(resolve_expr(ctx, item, lhs)?.as_ref())? {
+            Some(true) => Ok(Value::Bool(true)),
+            lhs => match (lhs, truth(resolve_expr(ctx, item, rhs)?.as_ref())?) {
+                (_, Some(true)) => Ok(Value::Bool(true)),
+                (Some(false), Some(false)) => Ok(Value::Bool(false)),
+                _ => Ok(logical(None)),
+            },
+        },
         _ => Err(anyhow!("No value")),
     }
 }
 
-pub fn resolve_expr<'a>(item: &'a Struct, expr: &Expr) -> Result<Cow<'a, Value>> {
+/// Readies `program` to be run with `ctx`: compiles its literal patterns,
+/// so a bad one is reported before any record is read, and hashes its large
+/// `in` lists under the collation each is compared with. It is called once
+/// the context is configured.
+pub fn prepare_program<'p>(ctx: &Context<'p>, program: &'p Program) -> Result<()> {
+    for rule in &program.rules {
+        if let Some(predicate) = &rule.predicate {
+            prepare_expr(ctx, predicate)?;
+        }
+        for part in rule.action.iter().flat_map(|action| &action.parts) {
+            if let TemplatePart::Expr(expr) = part {
+                prepare_expr(ctx, expr)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Prepares one expression of a program; see [`prepare_program`].
+pub fn prepare_expr<'p>(ctx: &Context<'p>, expr: &'p Expr) -> Result<()> {
+    prepare_collated(ctx, expr, ctx.collation)
+}
+
+fn prepare_collated<'p>(ctx: &Context<'p>, expr: &'p Expr, collation: Collation) -> Result<()> {
+    let collation = match expr {
+        Expr::Collate(_, name) => name.parse()?,
+        _ => collation,
+    };
+    if let Expr::In(_, list) = expr {
+        if let Expr::List(items) = list.as_ref() {
+            if let Some(set) = LiteralSet::build(items, collation) {
+                let key = list.as_ref() as *const Expr;
+                ctx.literal_sets.borrow_mut().insert(key, set);
+            }
+        }
+    }
+    match expr {
+        Expr::Regex(pattern) => ctx.compile_literal(pattern)?,
+        Expr::Match(_, pattern) | Expr::NotMatch(_, pattern) => {
+            if let Expr::String(pattern) = pattern.as_ref() {
+                ctx.compile_literal(pattern)?;
+            }
+        }
+        Expr::Like(_, pattern) => {
+            if let Expr::String(pattern) = pattern.as_ref() {
+                ctx.compile_literal(&pattern::like_regex(pattern, false))?;
+            }
+        }
+        Expr::ILike(_, pattern) => {
+            if let Expr::String(pattern) = pattern.as_ref() {
+                ctx.compile_literal(&pattern::like_regex(pattern, true))?;
+            }
+        }
+        Expr::Call(name, args) => match (name.as_str(), args.get(1)) {
+            ("replace" | "gsub", Some(Expr::String(pattern))) => ctx.compile_literal(pattern)?,
+            ("glob", Some(Expr::String(pattern))) => {
+                ctx.compile_literal(&pattern::glob_regex(pattern))?
+            }
+            _ => {}
+        },
+        _ => {}
+    }
+    expr.children()
+        .into_iter()
+        .try_for_each(|child| prepare_collated(ctx, child, collation))
+}
+
+pub fn resolve_expr<'a>(ctx: &Context, item: &'a Struct, expr: &Expr) -> Result<Cow<'a, Value>> {
     match expr {
-        Expr::Variable(_) => Ok(Cow::Borrowed(resolve_var(item, expr)?)),
+        Expr::Variable(_) => resolve_var(ctx, item, expr),
+        Expr::Index(base, index) => resolve_index(ctx, item, base, index),
+        Expr::Predicate(path, predicate) => {
+            Ok(Cow::Owned(resolve_predicate(ctx, item, path, predicate)?))
+        }
         Expr::Integer(v) => Ok(Cow::Owned(Value::Int(Int::I64(*v)))),
+        Expr::Float(v) => Ok(Cow::Owned(Value::Float(*v))),
+        Expr::Decimal(v) => Ok(Cow::Owned(Value::Decimal(Decimal::new(
+            v.mantissa(),
+            -i64::from(v.scale()),
+        )))),
         Expr::String(v) => Ok(Cow::Owned(Value::String(Str::from(v.to_owned())))),
-        _ => Ok(Cow::Owned(resolve_cond(item, expr)?)),
+        Expr::Boolean(v) => Ok(Cow::Owned(Value::Bool(*v))),
+        Expr::Null(type_name) => Ok(Cow::Owned(Value::Null(ion_type(*type_name)))),
+        Expr::Timestamp(v) => Ok(Cow::Owned(resolve_timestamp(v)?)),
+        Expr::Symbol(v) => Ok(Cow::Owned(Value::Symbol(Symbol::owned(v.as_str())))),
+        Expr::List(items) => {
+            let elements = items
+                .iter()
+                .map(|item_expr| Ok(resolve_expr(ctx, item, item_expr)?.into_owned().into()))
+                .collect::<Result<Vec<Element>>>()?;
+            Ok(Cow::Owned(Value::List(Sequence::new(elements))))
+        }
+        Expr::Call(name, args) => Ok(Cow::Owned(resolve_call(ctx, item, name, args)?)),
+        Expr::Cast(v, type_name, format) => Ok(Cow::Owned(resolve_cast(
+            ctx,
+            item,
+            v,
+            *type_name,
+            format.as_deref(),
+        )?)),
+        Expr::Collate(v, collation) => {
+            let outer = ctx.collate.replace(Some(collation.parse()?));
+            let value = resolve_expr(ctx, item, v);
+            ctx.collate.set(outer);
+            value
+        }
+        // Only the branch that is chosen is evaluated.
+        Expr::Coalesce(value, fallback) => {
+            let value = resolve_expr(ctx, item, value)?;
+            match value.as_ref() {
+                Value::Null(_) => resolve_expr(ctx, item, fallback),
+                _ => Ok(value),
+            }
+        }
+        Expr::Conditional(cond, then, otherwise) => {
+            // Like SQL's CASE, an unknown condition takes the else branch.
+            match truth(resolve_expr(ctx, item, cond)?.as_ref())? {
+                Some(true) => resolve_expr(ctx, item, then),
+                _ => resolve_expr(ctx, item, otherwise),
+            }
+        }
+        Expr::Negate(_)
+        | Expr::Add(_, _)
+        | Expr::Subtract(_, _)
+        | Expr::Multiply(_, _)
+        | Expr::Divide(_, _)
+        | Expr::Modulo(_, _) => Ok(Cow::Owned(resolve_arithmetic(ctx, item, expr)?)),
+        _ => Ok(Cow::Owned(resolve_cond(ctx, item, expr)?)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hawk_parser::parse_expr;
+
+    fn record() -> Element {
+        Element::struct_builder()
+            .with_field("0", "root")
+            .with_field("1", "x")
+            .with_field("2", "/bin/bash")
+            .build()
+            .into()
+    }
+
+    fn resolve(item: &Element, input: &str) -> Result<Value> {
+        let ctx = Context::default();
+        let (_, expr) = parse_expr(input).unwrap();
+        prepare_expr(&ctx, &expr)?;
+        resolve_expr(&ctx, item.as_struct().unwrap(), &expr).map(Cow::into_owned)
+    }
+
+    #[test]
+    fn test_resolve_index() {
+        let record = record();
+        assert_eq!(resolve(&record, "input[0]").unwrap(), Value::from("root"));
+        assert_eq!(
+            resolve(&record, "input[-1]").unwrap(),
+            Value::from("/bin/bash")
+        );
+        assert_eq!(resolve(&record, "$3[-4]").unwrap(), Value::from("b"));
+        assert_eq!(resolve(&record, "input[0][1]").unwrap(), Value::from("o"));
+        assert_eq!(
+            resolve(&record, "input[3]").unwrap(),
+            Value::Null(IonType::Null)
+        );
+        assert_eq!(
+            resolve(&record, "input[-4] is missing").unwrap(),
+            Value::Bool(true)
+        );
+    }
+
+    #[test]
+    fn test_resolve_record() {
+        let record = record();
+        let item = record.as_struct().unwrap();
+        let (_, expr) = parse_expr("$0 == \"root:x:/bin/bash\"").unwrap();
+        let ctx = Context::new(Some(":".to_string()));
+        assert_eq!(
+            resolve_expr(&ctx, item, &expr).unwrap().as_ref(),
+            &Value::Bool(true)
+        );
+        assert_eq!(resolve(&record, "$0").unwrap(), Value::Struct(item.clone()));
+
+        let mut builder = ::csv::ReaderBuilder::new();
+        builder.has_headers(true);
+        let input = "a,b,c\r\nx,\"y,z\",3\r\n\"multi\nline\",2,\n".as_bytes();
+        let mut records = csv::CsvIonIterator::new(&builder, input).unwrap();
+        let ctx = Context::new(Some(",".to_string()));
+        let record = records.next().unwrap();
+        ctx.set_line(records.line());
+        let (_, expr) = parse_expr("$0").unwrap();
+        assert_eq!(
+            resolve_expr(&ctx, record.as_struct().unwrap(), &expr)
+                .unwrap()
+                .as_ref(),
+            &Value::from("x,\"y,z\",3")
+        );
+        records.next().unwrap();
+        assert_eq!(records.line(), "\"multi\nline\",2,");
+        assert!(records.next().is_none());
+    }
+
+    #[test]
+    fn test_resolve_arithmetic() {
+        let record: Element = Element::struct_builder()
+            .with_field("0", "12")
+            .with_field("1", "100")
+            .with_field("2", "2.5")
+            .build()
+            .into();
+        assert_eq!(
+            resolve(&record, "$1 * $2 > 1000").unwrap(),
+            Value::Bool(true)
+        );
+        assert_eq!(resolve(&record, "$1 + 2 * 3").unwrap(), Value::from(18));
+        assert_eq!(resolve(&record, "$2-$1").unwrap(), Value::from(88));
+        assert_eq!(resolve(&record, "$2-1 > 0").unwrap(), Value::Bool(true));
+        let document = Element::read_one("{ x: 5, 'first-name': \"Ada\" }").unwrap();
+        assert_eq!(resolve(&document, "x-1").unwrap(), Value::from(4));
+        assert_eq!(
+            resolve(&document, "input.\"first-name\"").unwrap(),
+            Value::from("Ada")
+        );
+        assert_eq!(resolve(&record, "$2 / $1").unwrap(), Value::from(8));
+        assert_eq!(resolve(&record, "7 / 2").unwrap(), Value::from(3));
+        assert_eq!(resolve(&record, "-7 / 2").unwrap(), Value::from(-3));
+        assert_eq!(
+            resolve(&record, "7.0 / 2").unwrap(),
+            Value::Decimal(Decimal::new(35, -1))
+        );
+        assert_eq!(resolve(&record, "7 / 2e0").unwrap(), Value::Float(3.5));
+        assert_eq!(resolve(&record, "-$2 % 7").unwrap(), Value::from(-2));
+        assert_eq!(
+            resolve(&record, "$3 * 2").unwrap(),
+            Value::Decimal(Decimal::new(50, -1))
+        );
+        assert_eq!(
+            resolve(&record, "9223372036854775807 + 1").unwrap(),
+            Value::Int(Int::BigInt(BigInt::from(i64::MAX) + 1))
+        );
+        assert!(resolve(&record, "$1 / 0").is_err());
+        assert!(resolve(&record, "$1 + input").is_err());
+    }
+
+    #[test]
+    fn test_resolve_numeric_literals() {
+        let record: Element = Element::struct_builder()
+            .with_field("0", "9.990")
+            .with_field("1", "-4")
+            .build()
+            .into();
+        assert_eq!(resolve(&record, "$1 == 9.99").unwrap(), Value::Bool(true));
+        assert_eq!(resolve(&record, "$1 > 9.98999").unwrap(), Value::Bool(true));
+        assert_eq!(resolve(&record, "$2 < -3").unwrap(), Value::Bool(true));
+        assert_eq!(resolve(&record, "$2 > -4.5e0").unwrap(), Value::Bool(true));
+        assert_eq!(
+            resolve(&record, "1.5d2").unwrap(),
+            Value::Decimal(Decimal::new(15, 1))
+        );
+    }
+
+    #[test]
+    fn test_resolve_literals() {
+        let document = Element::read_one(
+            "{ active: true, deleted: null.bool, created: 2024-03-10T08:00Z, status: open }",
+        )
+        .unwrap();
+        assert_eq!(
+            resolve(&document, "active == true").unwrap(),
+            Value::Bool(true)
+        );
+        assert_eq!(
+            resolve(&document, "deleted is null").unwrap(),
+            Value::Bool(true)
+        );
+        assert_eq!(
+            resolve(&document, "deleted == null.bool").unwrap(),
+            Value::Null(IonType::Bool)
+        );
+        assert_eq!(
+            resolve(&document, "created > 2024-03-01T").unwrap(),
+            Value::Bool(true)
+        );
+        assert_eq!(
+            resolve(&document, "created < 2024-03-10T09:00+00:00").unwrap(),
+            Value::Bool(true)
+        );
+        assert_eq!(
+            resolve(&document, "status == `open`").unwrap(),
+            Value::Bool(true)
+        );
+        assert_eq!(
+            resolve(&document, "status == 'open'").unwrap(),
+            Value::Bool(true)
+        );
+        assert_eq!(
+            resolve(&document, "null.int").unwrap(),
+            Value::Null(IonType::Int)
+        );
+    }
+
+    #[test]
+    fn test_resolve_not() {
+        let record = record();
+        assert_eq!(
+            resolve(&record, "!($1 == \"root\" && $2 == \"y\")").unwrap(),
+            Value::Bool(true)
+        );
+        assert_eq!(
+            resolve(&record, "!$1 == \"root\"").unwrap(),
+            Value::Bool(false)
+        );
+        assert!(resolve(&record, "!$1").is_err());
+    }
+
+    #[test]
+    fn test_resolve_short_circuit() {
+        let record: Element = Element::struct_builder()
+            .with_field("0", "")
+            .with_field("1", "42")
+            .build()
+            .into();
+        assert!(resolve(&record, "$1 > 10").is_err());
+        assert_eq!(
+            resolve(&record, "$1 != \"\" && $1 > 10").unwrap(),
+            Value::Bool(false)
+        );
+        assert_eq!(
+            resolve(&record, "$1 == \"\" || $1 > 10").unwrap(),
+            Value::Bool(true)
+        );
+        assert_eq!(
+            resolve(&record, "$2 != \"\" && $2 > 10").unwrap(),
+            Value::Bool(true)
+        );
+    }
+
+    #[test]
+    fn test_resolve_match() {
+        let record = record();
+        let item = record.as_struct().unwrap();
+        let ctx = Context::new(Some(":".to_string()));
+        let resolve = |input| {
+            let (_, expr) = parse_expr(input).unwrap();
+            resolve_expr(&ctx, item, &expr).unwrap().into_owned()
+        };
+        assert_eq!(resolve("$1 ~ /^ro+t$/"), Value::Bool(true));
+        assert_eq!(resolve("$1 !~ /^ROOT$/i"), Value::Bool(false));
+        assert_eq!(resolve("$3 ~ \"sh$\""), Value::Bool(true));
+        assert_eq!(resolve("/x:\\//"), Value::Bool(true));
+
+        assert_eq!(
+            resolve("$3 ~ /^(\\/\\w+)\\/(?P<shell>\\w+)$/"),
+            Value::Bool(true)
+        );
+        assert_eq!(resolve("captures[0]"), Value::from("/bin/bash"));
+        assert_eq!(resolve("captures[1]"), Value::from("/bin"));
+        assert_eq!(resolve("captures.shell"), Value::from("bash"));
+        assert_eq!(
+            resolve("$1 ~ /^(x)?root/ && captures.$2 is null"),
+            Value::Bool(true)
+        );
+
+        resolve("$1 ~ /a/");
+        assert_eq!(resolve("captures.$1"), Value::from("root"));
+        ctx.clear_captures();
+        assert_eq!(
+            resolve("captures"),
+            Value::Struct(Element::struct_builder().build())
+        );
+
+        let (_, expr) = parse_expr("$1 ~ /(/").unwrap();
+        assert!(resolve_expr(&ctx, item, &expr).is_err());
+
+        // Literal patterns are compiled when the program is prepared, so a
+        // bad one is reported before any record reaches it.
+        let (_, expr) = parse_expr("$2 == \"y\" && $1 ~ /^ro+t$/").unwrap();
+        prepare_expr(&ctx, &expr).unwrap();
+        assert!(ctx.regexes.borrow().contains_key("^ro+t$"));
+        let (_, expr) = parse_expr("$2 == \"y\" && $1 ~ /(/").unwrap();
+        assert!(prepare_expr(&ctx, &expr).is_err());
+
+        // Patterns taken from records are only kept up to a limit.
+        for i in 0..DYNAMIC_REGEX_LIMIT + 10 {
+            ctx.regex(&format!("^{i}$")).unwrap();
+        }
+        assert!(ctx.dynamic_regexes.borrow().len() <= DYNAMIC_REGEX_LIMIT);
+    }
+
+    #[test]
+    fn test_resolve_cast() {
+        let record: Element = Element::struct_builder()
+            .with_field("0", "10")
+            .with_field("1", "9")
+            .with_field("2", "2.50")
+            .with_field("3", "15/03/2024")
+            .build()
+            .into();
+        assert_eq!(resolve(&record, "$1 < $2").unwrap(), Value::Bool(true));
+        assert_eq!(
+            resolve(&record, "int($1) < int($2)").unwrap(),
+            Value::Bool(false)
+        );
+        assert_eq!(
+            resolve(&record, "$1 as int < $2").unwrap(),
+            Value::Bool(false)
+        );
+        assert_eq!(
+            resolve(&record, "$3 as decimal").unwrap(),
+            Value::Decimal(Decimal::new(250, -2))
+        );
+        assert_eq!(resolve(&record, "int($3)").unwrap(), Value::from(2));
+        assert_eq!(resolve(&record, "int(-2.9e0)").unwrap(), Value::from(-2));
+        assert_eq!(resolve(&record, "float($3)").unwrap(), Value::Float(2.5));
+        assert_eq!(
+            resolve(&record, "$1 as bool").unwrap_err().to_string(),
+            "Cannot cast \"10\" to bool"
+        );
+        assert_eq!(
+            resolve(&record, "bool(\"TRUE\")").unwrap(),
+            Value::Bool(true)
+        );
+        assert_eq!(
+            resolve(&record, "string(12.5e0)").unwrap(),
+            Value::from("12.5")
+        );
+        assert_eq!(
+            resolve(&record, "symbol($2)").unwrap(),
+            Value::Symbol(Symbol::owned("9"))
+        );
+        assert_eq!(
+            resolve(&record, "null.string as int").unwrap(),
+            Value::Null(IonType::Int)
+        );
+        let march = resolve_timestamp("2024-03-15T00:00:00Z").unwrap();
+        assert_eq!(
+            resolve(&record, "timestamp($4, \"%d/%m/%Y\")").unwrap(),
+            march
+        );
+        assert_eq!(
+            resolve(
+                &record,
+                "timestamp(\"2024-03-15\") == timestamp(1710460800)"
+            )
+            .unwrap(),
+            Value::Bool(true)
+        );
+        assert_eq!(
+            resolve(&record, "string(timestamp($4, \"%d/%m/%Y\"), \"%Y-%m\")").unwrap(),
+            Value::from("2024-03")
+        );
+        assert!(resolve(&record, "timestamp($4)").is_err());
+        assert!(resolve(&record, "int($4)").is_err());
+        assert!(resolve(&record, "int($1, \"%d\")").is_err());
+        assert!(resolve(&record, "$1 as list").is_err());
+    }
+
+    #[test]
+    fn test_coercion_policy() {
+        let record: Element = Element::struct_builder()
+            .with_field("0", "abc")
+            .with_field("1", "10")
+            .with_field("2", "2024-03-01")
+            .build()
+            .into();
+        let item = record.as_struct().unwrap();
+        let resolve_with = |coercion, input| {
+            let ctx = Context {
+                coercion,
+                ..Default::default()
+            };
+            let (_, expr) = parse_expr(input).unwrap();
+            resolve_expr(&ctx, item, &expr).map(Cow::into_owned)
+        };
+
+        assert!(resolve_with(CoercionPolicy::Strict, "$1 < 5").is_err());
+        assert_eq!(
+            resolve_with(CoercionPolicy::Strict, "$2 == 10").unwrap(),
+            Value::Bool(true)
+        );
+        assert_eq!(
+            resolve_with(CoercionPolicy::Strict, "$3 > 2024-01-01T").unwrap(),
+            Value::Bool(true)
+        );
+        assert_eq!(
+            resolve_with(CoercionPolicy::Strict, "$3 < 2024-02-15T12:00Z").unwrap(),
+            Value::Bool(false)
+        );
+        assert_eq!(
+            resolve_with(CoercionPolicy::Lenient, "$1 > 5").unwrap(),
+            Value::Bool(true)
+        );
+        assert_eq!(
+            resolve_with(CoercionPolicy::Lenient, "$2 > 9").unwrap(),
+            Value::Bool(true)
+        );
+        assert_eq!(
+            resolve_with(CoercionPolicy::None, "$2 == 10").unwrap(),
+            Value::Bool(false)
+        );
+        assert_eq!(
+            resolve_with(CoercionPolicy::None, "int($2) == 10").unwrap(),
+            Value::Bool(true)
+        );
+        assert_eq!(
+            resolve_with(CoercionPolicy::None, "1 == 1.0").unwrap(),
+            Value::Bool(true)
+        );
+        assert_eq!(
+            resolve_with(CoercionPolicy::None, "2 > 1.5e0").unwrap(),
+            Value::Bool(true)
+        );
+        assert_eq!(
+            resolve_with(CoercionPolicy::None, "$1 < 5").unwrap(),
+            Value::Bool(false)
+        );
+        assert_eq!(
+            "lenient".parse::<CoercionPolicy>().unwrap(),
+            CoercionPolicy::Lenient
+        );
+        assert!("loose".parse::<CoercionPolicy>().is_err());
+    }
+
+    #[test]
+    fn test_numeric_tower() {
+        let record: Element = Element::struct_builder()
+            .with_field("0", "5")
+            .with_field("1", "9.990")
+            .with_field("2", "2.5")
+            .with_field("3", "99999999999999999999")
+            .build()
+            .into();
+        let truth = |input| match resolve(&record, input).unwrap() {
+            Value::Bool(v) => v,
+            value => panic!("{input} gave {value}"),
+        };
+
+        assert!(truth("$1 == 5"));
+        assert!(truth("$1 == 5.0"));
+        assert!(truth("$1 == 5e0"));
+        assert!(truth("$2 >= 9.99 && $2 <= 9.99"));
+        assert!(truth("$2 == 9.99"));
+        assert!(truth("$3 > 2"));
+        assert!(truth("$4 > 9223372036854775807"));
+        assert!(truth("$4 == 99999999999999999999.0"));
+        assert!(truth("2.5e0 == 2.5"));
+        assert!(truth("0.5e0 == 1 / 2.0"));
+        assert!(!truth("0.1e0 == 0.1"));
+        assert!(truth("0.1e0 > 0.1"));
+        assert!(truth("9007199254740993 > 9007199254740992e0"));
+        assert!(truth("9007199254740993 != 9007199254740992e0"));
+        assert!(truth("$4 * -1 < -9223372036854775808e0"));
+        assert!(truth("1.5 < 2 && 2 < 2.5e0 && 2.5e0 < 3.0"));
+        assert!(truth("float(\"inf\") > $4"));
+        assert!(truth("float(\"-inf\") < -1.0"));
+        assert!(!truth("float(\"nan\") == float(\"nan\")"));
+        assert!(!truth("float(\"nan\") < 1 || float(\"nan\") >= 1"));
+        assert!(truth("float(\"nan\") != 1.0"));
+
+        // Arithmetic still follows float semantics once a float is involved.
+        assert_eq!(resolve(&record, "1 + 0.5e0").unwrap(), Value::Float(1.5));
+        assert_eq!(
+            resolve(&record, "1 + 0.5").unwrap(),
+            Value::Decimal(Decimal::new(15, -1))
+        );
+    }
+
+    #[test]
+    fn test_null_semantics() {
+        let mut builder = ::csv::ReaderBuilder::new();
+        builder.has_headers(false).flexible(true);
+        let records: Vec<Element> = csv::CsvIonIterator::new(&builder, "a,1\nb\n".as_bytes())
+            .unwrap()
+            .collect();
+        let short = &records[1];
+        let document = Element::read_one("{ name: null.string, tags: [] }").unwrap();
+        let unknown = Value::Null(IonType::Bool);
+
+        assert_eq!(resolve(short, "$2").unwrap(), Value::Null(IonType::Null));
+        assert_eq!(resolve(short, "$2 > 0").unwrap(), unknown);
+        assert_eq!(resolve(short, "$2 == $2").unwrap(), unknown);
+        assert_eq!(resolve(short, "$2 != 1").unwrap(), unknown);
+        assert_eq!(resolve(short, "!($2 > 0)").unwrap(), unknown);
+        assert_eq!(resolve(short, "$2 ~ /1/").unwrap(), unknown);
+        assert_eq!(
+            resolve(short, "$2 + 1").unwrap(),
+            Value::Null(IonType::Null)
+        );
+        assert_eq!(resolve(&records[0], "$2 > 0").unwrap(), Value::Bool(true));
+
+        assert_eq!(resolve(short, "$2 > 0 && $1 == \"b\"").unwrap(), unknown);
+        assert_eq!(
+            resolve(short, "$2 > 0 && $1 == \"a\"").unwrap(),
+            Value::Bool(false)
+        );
+        assert_eq!(
+            resolve(short, "$1 == \"a\" && $2 > 0").unwrap(),
+            Value::Bool(false)
+        );
+        assert_eq!(
+            resolve(short, "$2 > 0 || $1 == \"b\"").unwrap(),
+            Value::Bool(true)
+        );
+        assert_eq!(resolve(short, "$2 > 0 || $1 == \"a\"").unwrap(), unknown);
+        assert_eq!(resolve(short, "$1 == \"a\" || $2 > 0").unwrap(), unknown);
+        assert_eq!(resolve(short, "null && null").unwrap(), unknown);
+
+        assert_eq!(resolve(short, "$2 is null").unwrap(), Value::Bool(true));
+        assert_eq!(resolve(short, "$2 is missing").unwrap(), Value::Bool(true));
+        assert_eq!(resolve(short, "$1 is not null").unwrap(), Value::Bool(true));
+        assert_eq!(
+            resolve(short, "$2 + 1 is missing").unwrap(),
+            Value::Bool(false)
+        );
+        assert_eq!(
+            resolve(&document, "name is null").unwrap(),
+            Value::Bool(true)
+        );
+        assert_eq!(
+            resolve(&document, "name is missing").unwrap(),
+            Value::Bool(false)
+        );
+        assert_eq!(
+            resolve(&document, "tags[0] is missing").unwrap(),
+            Value::Bool(true)
+        );
+        assert_eq!(
+            resolve(&document, "captures.$1 is missing").unwrap(),
+            Value::Bool(true)
+        );
+
+        assert!(resolve(short, "$1 && true").is_err());
+    }
+
+    #[test]
+    fn test_resolve_conditional() {
+        let record: Element = Element::struct_builder()
+            .with_field("0", "7")
+            .with_field("1", "")
+            .build()
+            .into();
+
+        assert_eq!(
+            resolve(&record, "$3 ?? \"n/a\"").unwrap(),
+            Value::from("n/a")
+        );
+        assert_eq!(resolve(&record, "$1 ?? 0").unwrap(), Value::from("7"));
+        assert_eq!(resolve(&record, "$2 ?? 0").unwrap(), Value::from(""));
+        assert_eq!(resolve(&record, "$3 ?? $4 ?? 0").unwrap(), Value::from(0));
+        assert_eq!(
+            resolve(&record, "$1 > 5 ? \"big\" : \"small\"").unwrap(),
+            Value::from("big")
+        );
+        assert_eq!(
+            resolve(&record, "$3 > 5 ? \"big\" : \"small\"").unwrap(),
+            Value::from("small")
+        );
+        assert_eq!(
+            resolve(
+                &record,
+                "case when $1 < 5 then `low` when $1 < 10 then `mid` else `high` end"
+            )
+            .unwrap(),
+            Value::Symbol(Symbol::owned("mid"))
+        );
+        assert_eq!(
+            resolve(&record, "case when $1 > 10 then 1 end").unwrap(),
+            Value::Null(IonType::Null)
+        );
+
+        // Branches that are not taken are not evaluated.
+        assert_eq!(resolve(&record, "$1 ?? $1 / 0").unwrap(), Value::from("7"));
+        assert_eq!(
+            resolve(&record, "true ? 1 : 1 / 0").unwrap(),
+            Value::from(1)
+        );
+        assert_eq!(
+            resolve(&record, "case when true then 1 when 1 / 0 then 2 end").unwrap(),
+            Value::from(1)
+        );
+        assert!(resolve(&record, "false ? 1 : 1 / 0").is_err());
+        assert!(resolve(&record, "$1 ? 1 : 2").is_err());
+    }
+
+    #[test]
+    fn test_resolve_membership() {
+        let document = Element::read_one(
+            "{ id: \"7\", name: \"x\", status: open, tags: [a, b], score: 2.5e0, none: null }",
+        )
+        .unwrap();
+        let truth = |input: &str| resolve(&document, input).unwrap();
+        let large = (10..40)
+            .map(|v| v.to_string())
+            .collect::<Vec<_>>()
+            .join(", ");
+
+        assert_eq!(
+            truth("[1, id, [2]]"),
+            Element::read_one("[1, \"7\", [2]]")
+                .unwrap()
+                .value()
+                .clone()
+        );
+        assert_eq!(truth("id in (1, 7, 9)"), Value::Bool(true));
+        assert_eq!(truth("id in [5, \"7\"]"), Value::Bool(true));
+        assert_eq!(truth("id not in (1, 2)"), Value::Bool(true));
+        assert_eq!(truth("status in (`open`, `closed`)"), Value::Bool(true));
+        assert_eq!(truth("`b` in tags"), Value::Bool(true));
+        assert_eq!(truth("score in (2.50, 3)"), Value::Bool(true));
+        assert_eq!(truth(&format!("id in ({large})")), Value::Bool(false));
+        assert_eq!(truth(&format!("id * 5 in ({large})")), Value::Bool(true));
+        assert_eq!(
+            truth(&format!("score * 10 in ({large})")),
+            Value::Bool(true)
+        );
+
+        let unknown = Value::Null(IonType::Bool);
+        assert_eq!(truth("none in (1, 2)"), unknown);
+        assert_eq!(truth("id in (1, none)"), unknown);
+        assert_eq!(truth("id not in (1, none)"), unknown);
+        assert_eq!(truth("id in (7, none)"), Value::Bool(true));
+        assert_eq!(truth("id in missing"), unknown);
+
+        assert_eq!(truth("id between 5 and 10"), Value::Bool(true));
+        assert_eq!(truth("id between 7 and 7.0"), Value::Bool(true));
+        assert_eq!(truth("score not between 3 and 4"), Value::Bool(true));
+        assert_eq!(truth("score between 1 and none"), unknown);
+        assert_eq!(truth("score between 3 and none"), Value::Bool(false));
+
+        assert!(resolve(&document, "id in status").is_err());
+        assert_eq!(truth(&format!("status in ({large})")), Value::Bool(false));
+        assert!(resolve(&document, "name in (1, 2)").is_err());
+        assert!(resolve(&document, &format!("name in ({large})")).is_err());
+
+        // Large literal lists are hashed when the program is prepared, under
+        // the collation they are compared with.
+        let words = (0..20)
+            .map(|v| format!("\"K{v}\""))
+            .collect::<Vec<_>>()
+            .join(", ");
+        let (_, expr) = parse_expr(&format!("(\"k7\" in ({words})) collate nocase")).unwrap();
+        let ctx = Context::default();
+        prepare_expr(&ctx, &expr).unwrap();
+        let Expr::Collate(test, _) = &expr else {
+            panic!("{expr:?} is not collated");
+        };
+        let Expr::In(_, list) = test.as_ref() else {
+            panic!("{test:?} is not a membership test");
+        };
+        assert!(matches!(
+            ctx.literal_set(list).as_deref(),
+            Some(LiteralSet::Texts(_))
+        ));
+        let item = document.as_struct().unwrap();
+        for ctx in [&ctx, &Context::default()] {
+            assert_eq!(
+                resolve_expr(ctx, item, &expr).unwrap().as_ref(),
+                &Value::Bool(true)
+            );
+        }
+        assert_eq!(truth(&format!("\"k7\" in ({words})")), Value::Bool(false));
+    }
+
+    #[test]
+    fn test_resolve_like() {
+        let record = record();
+        let ctx = Context::default();
+        let item = record.as_struct().unwrap();
+        let resolve = |input| {
+            let (_, expr) = parse_expr(input).unwrap();
+            resolve_expr(&ctx, item, &expr).unwrap().into_owned()
+        };
+
+        assert_eq!(resolve("$1 like \"ro%\""), Value::Bool(true));
+        assert_eq!(resolve("$1 like \"ro\""), Value::Bool(false));
+        assert_eq!(resolve("$1 like \"r__t\""), Value::Bool(true));
+        assert_eq!(resolve("$1 like \"ROOT\""), Value::Bool(false));
+        assert_eq!(resolve("$1 ilike \"ROOT\""), Value::Bool(true));
+        assert_eq!(resolve("$3 not like \"%/bash\""), Value::Bool(false));
+        assert_eq!(resolve("$3 like $1"), Value::Bool(false));
+        assert_eq!(resolve("$4 like \"%\""), Value::Null(IonType::Bool));
+        assert_eq!(resolve("glob($3, \"/bin/*sh\")"), Value::Bool(true));
+        assert_eq!(resolve("glob($3, \"/bin/[!b]*\")"), Value::Bool(false));
+
+        // Literal patterns are compiled once, when the program is prepared.
+        let (_, expr) = parse_expr("$1 like \"ro%\" && glob($3, \"/bin/*sh\")").unwrap();
+        let ctx = Context::default();
+        prepare_expr(&ctx, &expr).unwrap();
+        assert_eq!(ctx.regexes.borrow().len(), 2);
+        resolve_expr(&ctx, item, &expr).unwrap();
+        assert!(ctx.dynamic_regexes.borrow().is_empty());
+    }
+
+    #[test]
+    fn test_resolve_collate() {
+        let document = Element::read_one(
+            "{ name: \"Ｊｏｓé\", file: \"Report9.csv\", status: OPEN, tags: [\"Open\"] }",
+        )
+        .unwrap();
+        let item = document.as_struct().unwrap();
+        let resolve_with = |collation: &str, input: &str| {
+            let ctx = Context {
+                collation: collation.parse().unwrap(),
+                ..Default::default()
+            };
+            let (_, expr) = parse_expr(input).unwrap();
+            resolve_expr(&ctx, item, &expr).unwrap().into_owned()
+        };
+        let resolve = |input: &str| resolve_with("binary", input);
+        let statuses = (0..20).map(|v| format!("\"s{v}\"")).collect::<Vec<_>>();
+
+        assert_eq!(resolve("status == `open`"), Value::Bool(false));
+        assert_eq!(
+            resolve("status == `open` collate nocase"),
+            Value::Bool(true)
+        );
+        assert_eq!(
+            resolve("name == \"josé\" collate nocase"),
+            Value::Bool(false)
+        );
+        assert_eq!(
+            resolve("name == \"jose\u{301}\" collate nfkc+nocase"),
+            Value::Bool(true)
+        );
+        assert_eq!(resolve("file < \"report10.csv\""), Value::Bool(true));
+        assert_eq!(
+            resolve("file < \"report10.csv\" collate nocase+natural"),
+            Value::Bool(true)
+        );
+        assert_eq!(
+            resolve("file > \"report10.csv\" collate nocase"),
+            Value::Bool(true)
+        );
+        assert_eq!(
+            resolve("(\"OPEN\" in tags && `open` between `a` and `z`) collate nocase"),
+            Value::Bool(true)
+        );
+        assert_eq!(
+            resolve(&format!(
+                "\"S7\" in ({}) collate nocase",
+                statuses.join(", ")
+            )),
+            Value::Bool(true)
+        );
+        assert_eq!(
+            resolve(&format!("\"S7\" in ({})", statuses.join(", "))),
+            Value::Bool(false)
+        );
+
+        // A query-wide collation applies unless an operator overrides it.
+        assert_eq!(
+            resolve_with("nocase", "status == `open`"),
+            Value::Bool(true)
+        );
+        assert_eq!(
+            resolve_with("nocase", "status == `open` collate binary"),
+            Value::Bool(false)
+        );
+        assert_eq!(
+            resolve_with("nocase", "name > \"ｊｏｓ\""),
+            Value::Bool(true)
+        );
+
+        let (_, expr) = parse_expr("name == name collate fuzzy").unwrap();
+        assert!(resolve_expr(&Context::default(), item, &expr).is_err());
+    }
+
+    #[test]
+    fn test_resolve_var() {
+        let mut builder = ::csv::ReaderBuilder::new();
+        builder.has_headers(true);
+        let input = "name,shell\nroot,/bin/bash\n".as_bytes();
+        let record = csv::CsvIonIterator::new(&builder, input)
+            .unwrap()
+            .next()
+            .unwrap();
+        assert_eq!(resolve(&record, "name").unwrap(), Value::from("root"));
+        assert_eq!(resolve(&record, "$2").unwrap(), Value::from("/bin/bash"));
+        assert_eq!(
+            resolve(&record, "home").unwrap(),
+            Value::Null(IonType::Null)
+        );
+
+        let document = Element::read_one(
+            "{ user: { address: { city: \"Leeds\" } }, tags: [{ name: a }, { name: b }] }",
+        )
+        .unwrap();
+        assert_eq!(
+            resolve(&document, "user.address.city").unwrap(),
+            Value::from("Leeds")
+        );
+        assert_eq!(
+            &resolve(&document, "tags.name").unwrap(),
+            Element::read_one("[a, b]").unwrap().value()
+        );
+        assert_eq!(
+            resolve(&document, "user.address.zip is missing").unwrap(),
+            Value::Bool(true)
+        );
+    }
+
+    #[test]
+    fn test_resolve_predicate() {
+        let document = Element::read_one(
+            "{ id: 7, orders: [{ id: 1, total: 50 }, { id: 2, total: 150 }, { id: 3, total: 300 }] }",
+        )
+        .unwrap();
+        let matches = resolve(&document, "orders[$2 > 100]").unwrap();
+        let expected = Element::read_one("[{ id: 2, total: 150 }, { id: 3, total: 300 }]").unwrap();
+        assert_eq!(&matches, expected.value());
+        assert_eq!(
+            resolve(&document, "orders[$2 > 100][-1][0]").unwrap(),
+            Value::from(3)
+        );
+
+        let nested = Element::read_one(
+            "{ users: [{ orders: [{ total: 10 }] }, { orders: [{ total: 20 }, { total: 5 }] }] }",
+        )
+        .unwrap();
+        let matches = resolve(&nested, "users.orders[$1 >= 10]").unwrap();
+        let expected = Element::read_one("[{ total: 10 }, { total: 20 }]").unwrap();
+        assert_eq!(&matches, expected.value());
     }
 }
@@ -0,0 +1,123 @@
+/// The regex for a `like` pattern: `%` matches any run of characters, `_`
+/// any one character, and a backslash makes the character after it literal.
+/// The whole text must match.
+pub fn like_regex(pattern: &str, case_insensitive: bool) -> String {
+    let mut regex = String::from(if case_insensitive { "(?is)^" } else { "(?s)^" });
+    let mut chars = pattern.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '%' => regex.push_str(".*"),
+            '_' => regex.push('.'),
+            '\\' => {
+                if let Some(c) = chars.next() {
+                    regex.push_str(&regex::escape(&c.to_string()));
+                }
+            }
+            c => regex.push_str(&regex::escape(&c.to_string())),
+        }
+    }
+    regex.push('$');
+    regex
+}
+
+/// The regex for a shell glob: `*` matches any run of characters, `?` any
+/// one character, and `[abc]`, `[a-z]` or `[!abc]` one character of (or not
+/// of) a set. A backslash makes the character after it literal, as does a
+/// `[` without a closing `]`. The whole text must match.
+pub fn glob_regex(pattern: &str) -> String {
+    let mut regex = String::from("(?s)^");
+    let chars: Vec<char> = pattern.chars().collect();
+    let mut i = 0;
+    while i < chars.len() {
+        match chars[i] {
+            '*' => regex.push_str(".*"),
+            '?' => regex.push('.'),
+            '\\' if i + 1 < chars.len() => {
+                i += 1;
+                regex.push_str(&regex::escape(&chars[i].to_string()));
+            }
+            '[' => match glob_class(&chars[i + 1..]) {
+                Some((class, length)) => {
+                    regex.push_str(&class);
+                    i += length;
+                }
+                None => regex.push_str(r"\["),
+            },
+            c => regex.push_str(&regex::escape(&c.to_string())),
+        }
+        i += 1;
+    }
+    regex.push('$');
+    regex
+}
+
+/// Translates the body of a `[...]` set, given the characters after its
+/// `[`. Returns the regex class and the number of characters it consumed,
+/// including the closing `]`, or `None` when the set is never closed. As in
+/// the shell, a `]` first in the set is a member rather than its end.
+fn glob_class(chars: &[char]) -> Option<(String, usize)> {
+    let negated = matches!(chars.first(), Some('!' | '^'));
+    let start = usize::from(negated);
+    let end = start + 1 + chars.get(start + 1..)?.iter().position(|&c| c == ']')?;
+    let mut class = String::from(if negated { "[^" } else { "[" });
+    for &c in &chars[start..end] {
+        match c {
+            '\\' | '[' | ']' | '&' | '~' | '^' => {
+                class.push('\\');
+                class.push(c);
+            }
+            c => class.push(c),
+        }
+    }
+    class.push(']');
+    Some((class, end + 1))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use regex::Regex;
+
+    fn like(text: &str, pattern: &str) -> bool {
+        Regex::new(&like_regex(pattern, false))
+            .unwrap()
+            .is_match(text)
+    }
+
+    fn glob(text: &str, pattern: &str) -> bool {
+        Regex::new(&glob_regex(pattern)).unwrap().is_match(text)
+    }
+
+    #[test]
+    fn test_like_regex() {
+        assert!(like("alpha", "a%"));
+        assert!(like("a", "a%"));
+        assert!(!like("beta", "a%"));
+        assert!(like("cat", "c_t"));
+        assert!(!like("coat", "c_t"));
+        assert!(like("line\nbreak", "line%"));
+        assert!(like("50%", "50\\%"));
+        assert!(!like("500", "50\\%"));
+        assert!(like("a.b(c)", "a.b(%)"));
+        assert!(!like("axb", "a.b"));
+        assert!(Regex::new(&like_regex("AB%", true))
+            .unwrap()
+            .is_match("abc"));
+    }
+
+    #[test]
+    fn test_glob_regex() {
+        assert!(glob("report.csv", "*.csv"));
+        assert!(!glob("report.csv.gz", "*.csv"));
+        assert!(glob("file1", "file?"));
+        assert!(glob("b", "[abc]"));
+        assert!(glob("q", "[a-z]"));
+        assert!(!glob("b", "[!abc]"));
+        assert!(glob("d", "[^abc]"));
+        assert!(glob("]", "[]x]"));
+        assert!(glob("^", "[x^]"));
+        assert!(glob("[ab", "[ab"));
+        assert!(glob("*", "\\*"));
+        assert!(!glob("x", "\\*"));
+    }
+}
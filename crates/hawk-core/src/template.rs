@@ -0,0 +1,86 @@
+use anyhow::Result;
+use hawk_parser::template::{Template, TemplatePart};
+use ion_rs::{element::Value, external::bigdecimal::BigDecimal, types::Struct};
+
+use crate::source::{resolve_expr, Context};
+
+/// Text substituted for a template hole. Strings and symbols are written
+/// without Ion quoting, numbers in plain decimal notation (`4`, `3.25`,
+/// `2.50`) and nulls of every type as `null`; everything else uses its Ion
+/// text form.
+pub fn value_text(value: &Value) -> String {
+    match value {
+        Value::String(v) => v.text().to_string(),
+        Value::Symbol(v) => v.text().unwrap_or_default().to_string(),
+        Value::Float(v) => v.to_string(),
+        // Negative zero is the only Decimal that BigDecimal cannot represent.
+        Value::Decimal(v) => {
+            BigDecimal::try_from(v.clone()).map_or_else(|_| "-0".to_string(), |v| v.to_string())
+        }
+        Value::Null(_) => "null".to_string(),
+        _ => value.to_string(),
+    }
+}
+
+pub fn render_template(ctx: &Context, item: &Struct, template: &Template) -> Result<String> {
+    let mut output = String::new();
+    for part in &template.parts {
+        match part {
+            TemplatePart::Literal(text) => output.push_str(text),
+            TemplatePart::Expr(expr) => {
+                let value = resolve_expr(ctx, item, expr)?;
+                output.push_str(&value_text(value.as_ref()))
+            }
+        }
+    }
+    Ok(output)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hawk_parser::template::parse_template;
+    use ion_rs::element::Element;
+
+    #[test]
+    fn test_render_template() {
+        let element: Element = Element::struct_builder()
+            .with_field("0", "root")
+            .with_field("1", "/bin/bash")
+            .build()
+            .into();
+        let (_, template) = parse_template("{{ $1 }} uses {{$2}} ({{ $1 == \"root\" }})").unwrap();
+        let output =
+            render_template(&Context::default(), element.as_struct().unwrap(), &template).unwrap();
+        assert_eq!(output, "root uses /bin/bash (true)");
+    }
+
+    #[test]
+    fn test_render_numbers() {
+        let element: Element = Element::struct_builder()
+            .with_field("0", "16")
+            .with_field("1", "2.675")
+            .build()
+            .into();
+        let render = |input| {
+            let (_, template) = parse_template(input).unwrap();
+            render_template(&Context::default(), element.as_struct().unwrap(), &template).unwrap()
+        };
+        assert_eq!(render("{{ $1 + 1 }} {{ int($2) }}"), "17 2");
+        assert_eq!(
+            render("{{ 9223372036854775807 + 1 }}"),
+            "9223372036854775808"
+        );
+        assert_eq!(render("{{ sqrt($1) }} {{ float(\"3.25\") }}"), "4 3.25");
+        assert_eq!(render("{{ 1e-7 }} {{ -0e0 }}"), "0.0000001 -0");
+        assert_eq!(
+            render("{{ round(2.5) }} {{ 2.50 }} {{ $2 * 2 }}"),
+            "3 2.50 5.350"
+        );
+        assert_eq!(render("{{ 1.5d3 }} {{ 1d-3 }}"), "1500 0.001");
+        assert_eq!(
+            render("{{ $3 > 1 }} {{ null.int }} {{ $3 }}"),
+            "null null null"
+        );
+    }
+}
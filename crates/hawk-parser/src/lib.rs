@@ -1,19 +1,46 @@
 use nom::{
     branch::alt,
-    bytes::complete::{tag, take_while, take_while1},
-    character::complete::{alpha1, char, digit1, multispace0},
-    combinator::{map, map_res, opt, recognize},
-    multi::{many0, separated_list1},
-    sequence::{delimited, pair, preceded},
+    bytes::complete::{is_not, tag, take_while, take_while1, take_while_m_n},
+    character::complete::{alpha1, anychar, char, digit1, multispace0, one_of, satisfy},
+    combinator::{consumed, map, map_opt, map_res, not, opt, recognize, value},
+    multi::{many0, many1, separated_list0, separated_list1},
+    sequence::{delimited, pair, preceded, terminated, tuple},
     IResult,
 };
-// use rust_decimal::Decimal;
+use rust_decimal::Decimal;
+
+pub mod program;
+pub mod template;
+
+/// The Ion data types, as named in typed nulls such as `null.int`.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum TypeName {
+    Null,
+    Bool,
+    Int,
+    Float,
+    Decimal,
+    Timestamp,
+    Symbol,
+    String,
+    Clob,
+    Blob,
+    List,
+    SExp,
+    Struct,
+}
 
 #[derive(Debug, PartialEq)]
 pub enum Expr {
     Integer(i64),
     String(String),
-    // Decimal(Decimal),
+    Float(f64),
+    Decimal(Decimal),
+    Boolean(bool),
+    Null(TypeName),
+    Timestamp(String),
+    Symbol(String),
+    Regex(String),
     Variable(String),
     Equal(Box<Expr>, Box<Expr>),
     NotEqual(Box<Expr>, Box<Expr>),
@@ -24,10 +51,236 @@ pub enum Expr {
     And(Box<Expr>, Box<Expr>),
     Or(Box<Expr>, Box<Expr>),
     Predicate(String, Box<Expr>),
+    Index(Box<Expr>, Box<Expr>),
+    Negate(Box<Expr>),
+    Add(Box<Expr>, Box<Expr>),
+    Subtract(Box<Expr>, Box<Expr>),
+    Multiply(Box<Expr>, Box<Expr>),
+    Divide(Box<Expr>, Box<Expr>),
+    Modulo(Box<Expr>, Box<Expr>),
+    Not(Box<Expr>),
+    Match(Box<Expr>, Box<Expr>),
+    NotMatch(Box<Expr>, Box<Expr>),
+    Call(String, Vec<Expr>),
+    /// `int($2)`, `$2 as decimal` or `timestamp($4, "%d/%m/%Y")`: a value
+    /// converted to a type, with an optional format for timestamps.
+    Cast(Box<Expr>, TypeName, Option<Box<Expr>>),
+    /// `expr is null`: true for null and missing values alike.
+    IsNull(Box<Expr>),
+    /// `expr is missing`: true when a field or index is absent from the
+    /// record, as opposed to present with a null value.
+    IsMissing(Box<Expr>),
+    /// `[a, b, ...]`, a list literal.
+    List(Vec<Expr>),
+    /// `x in (a, b, ...)` or `x in list`: whether `x` equals any member.
+    In(Box<Expr>, Box<Expr>),
+    /// `x between low and high`, both bounds included.
+    Between(Box<Expr>, Box<Expr>, Box<Expr>),
+    /// `x like pattern`: SQL pattern matching, where `%` matches any run of
+    /// characters and `_` any one character.
+    Like(Box<Expr>, Box<Expr>),
+    /// `x ilike pattern`: `like`, ignoring case.
+    ILike(Box<Expr>, Box<Expr>),
+    /// `cond collate nocase`: `cond` with its string comparisons made under
+    /// the named collation, e.g. `nocase`, `natural` or `nfkc+nocase`.
+    Collate(Box<Expr>, String),
+    /// `a ?? b`: `a` unless it is null or missing, in which case `b`.
+    Coalesce(Box<Expr>, Box<Expr>),
+    /// `cond ? a : b`. `case when` chains are read as nested conditionals.
+    Conditional(Box<Expr>, Box<Expr>, Box<Expr>),
+}
+
+impl Expr {
+    /// The expressions this one is built from, in source order.
+    pub fn children(&self) -> Vec<&Expr> {
+        match self {
+            Expr::Integer(_)
+            | Expr::String(_)
+            | Expr::Float(_)
+            | Expr::Decimal(_)
+            | Expr::Boolean(_)
+            | Expr::Null(_)
+            | Expr::Timestamp(_)
+            | Expr::Symbol(_)
+            | Expr::Regex(_)
+            | Expr::Variable(_) => vec![],
+            Expr::Predicate(_, v)
+            | Expr::Negate(v)
+            | Expr::Not(v)
+            | Expr::IsNull(v)
+            | Expr::IsMissing(v)
+            | Expr::Collate(v, _) => vec![v],
+            Expr::Equal(lhs, rhs)
+            | Expr::NotEqual(lhs, rhs)
+            | Expr::LessThan(lhs, rhs)
+            | Expr::LessThanOrEqual(lhs, rhs)
+            | Expr::GreaterThan(lhs, rhs)
+            | Expr::GreaterThanOrEqual(lhs, rhs)
+            | Expr::And(lhs, rhs)
+            | Expr::Or(lhs, rhs)
+            | Expr::Index(lhs, rhs)
+            | Expr::Add(lhs, rhs)
+            | Expr::Subtract(lhs, rhs)
+            | Expr::Multiply(lhs, rhs)
+            | Expr::Divide(lhs, rhs)
+            | Expr::Modulo(lhs, rhs)
+            | Expr::Match(lhs, rhs)
+            | Expr::NotMatch(lhs, rhs)
+            | Expr::In(lhs, rhs)
+            | Expr::Like(lhs, rhs)
+            | Expr::ILike(lhs, rhs)
+            | Expr::Coalesce(lhs, rhs) => vec![lhs, rhs],
+            Expr::Call(_, args) | Expr::List(args) => args.iter().collect(),
+            Expr::Cast(v, _, format) => std::iter::once(v).chain(format).map(|v| &**v).collect(),
+            Expr::Between(a, b, c) | Expr::Conditional(a, b, c) => vec![a, b, c],
+        }
+    }
 }
 
+/// Parses a numeric literal the way Ion reads them: `12` is an integer,
+/// `12.5` and `1.25d1` are exact decimals, and `1.25e1` is a float.
 pub fn parse_number(input: &str) -> IResult<&str, Expr> {
-    map_res(digit1, |s: &str| s.parse().map(Expr::Integer))(input)
+    let (input, (text, (_, (fraction, exponent)))) = consumed(pair(
+        preceded(opt(char('-')), digit1),
+        pair(
+            opt(preceded(char('.'), digit1)),
+            opt(pair(
+                one_of("eEdD"),
+                recognize(pair(opt(one_of("+-")), digit1)),
+            )),
+        ),
+    ))(input)?;
+
+    let expr = match (fraction, exponent) {
+        (None, None) => text.parse().map(Expr::Integer).ok(),
+        (_, Some(('e' | 'E', _))) => text.parse().map(Expr::Float).ok(),
+        (_, Some((_, exponent))) => {
+            let mantissa = &text[..text.len() - exponent.len() - 1];
+            Decimal::from_scientific(&format!("{mantissa}e{exponent}"))
+                .map(Expr::Decimal)
+                .ok()
+        }
+        (Some(_), None) => Decimal::from_str_exact(text).map(Expr::Decimal).ok(),
+    };
+    match expr {
+        Some(expr) => Ok((input, expr)),
+        None => Err(nom::Err::Error(nom::error::Error::new(
+            text,
+            nom::error::ErrorKind::MapRes,
+        ))),
+    }
+}
+
+fn is_identifier_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
+/// Matches `word` only when it is not the start of a longer identifier.
+pub fn keyword<'a>(word: &'static str) -> impl FnMut(&'a str) -> IResult<&'a str, &'a str> {
+    terminated(tag(word), not(satisfy(is_identifier_char)))
+}
+
+pub fn parse_type_name(input: &str) -> IResult<&str, TypeName> {
+    alt((
+        value(TypeName::Null, keyword("null")),
+        value(TypeName::Bool, keyword("bool")),
+        value(TypeName::Int, keyword("int")),
+        value(TypeName::Float, keyword("float")),
+        value(TypeName::Decimal, keyword("decimal")),
+        value(TypeName::Timestamp, keyword("timestamp")),
+        value(TypeName::Symbol, keyword("symbol")),
+        value(TypeName::String, keyword("string")),
+        value(TypeName::Clob, keyword("clob")),
+        value(TypeName::Blob, keyword("blob")),
+        value(TypeName::List, keyword("list")),
+        value(TypeName::SExp, keyword("sexp")),
+        value(TypeName::Struct, keyword("struct")),
+    ))(input)
+}
+
+pub fn parse_boolean(input: &str) -> IResult<&str, Expr> {
+    map(
+        alt((value(true, keyword("true")), value(false, keyword("false")))),
+        Expr::Boolean,
+    )(input)
+}
+
+/// Parses `null` or an Ion typed null such as `null.int`.
+pub fn parse_null(input: &str) -> IResult<&str, Expr> {
+    map(
+        preceded(keyword("null"), opt(preceded(char('.'), parse_type_name))),
+        |type_name| Expr::Null(type_name.unwrap_or(TypeName::Null)),
+    )(input)
+}
+
+fn digits<'a>(count: usize) -> impl FnMut(&'a str) -> IResult<&'a str, &'a str> {
+    take_while_m_n(count, count, |c: char| c.is_ascii_digit())
+}
+
+/// Recognises an Ion timestamp literal (`2024T`, `2024-01T`, `2024-01-01`,
+/// `2024-01-01T12:30:00.5Z`, ...); its value is read by hawk-core.
+pub fn parse_timestamp(input: &str) -> IResult<&str, Expr> {
+    let offset = alt((
+        tag("Z"),
+        recognize(tuple((one_of("+-"), digits(2), char(':'), digits(2)))),
+    ));
+    let time = tuple((
+        digits(2),
+        char(':'),
+        digits(2),
+        opt(tuple((char(':'), digits(2), opt(pair(char('.'), digit1))))),
+    ));
+    let day = tuple((
+        char('-'),
+        digits(2),
+        char('-'),
+        digits(2),
+        opt(pair(char('T'), opt(pair(time, offset)))),
+    ));
+    let month = tuple((char('-'), digits(2), char('T')));
+
+    map(
+        terminated(
+            recognize(pair(
+                digits(4),
+                alt((recognize(day), recognize(month), tag("T"))),
+            )),
+            not(satisfy(is_identifier_char)),
+        ),
+        |text: &str| Expr::Timestamp(text.to_string()),
+    )(input)
+}
+
+/// Parses a backtick-quoted Ion symbol, e.g. `` `active` ``.
+pub fn parse_symbol(input: &str) -> IResult<&str, Expr> {
+    map(
+        delimited(char('`'), take_while(|c| c != '`'), char('`')),
+        |text: &str| Expr::Symbol(text.to_string()),
+    )(input)
+}
+
+/// Parses a `/pattern/flags` regex literal. `\/` stands for a literal slash;
+/// other escapes are left for the regex engine. The `i`, `m`, `s` and `x`
+/// flags are folded into the pattern as an inline `(?flags)` group.
+pub fn parse_regex(input: &str) -> IResult<&str, Expr> {
+    let (input, (pattern, flags)) = pair(
+        delimited(
+            char('/'),
+            recognize(many0(alt((
+                recognize(pair(char('\\'), anychar)),
+                is_not("\\/"),
+            )))),
+            char('/'),
+        ),
+        take_while(|c| "imsx".contains(c)),
+    )(input)?;
+
+    let pattern = pattern.replace("\\/", "/");
+    let pattern = match flags {
+        "" => pattern,
+        flags => format!("(?{flags}){pattern}"),
+    };
+    Ok((input, Expr::Regex(pattern)))
 }
 
 pub fn parse_string(input: &str, quote_char: char) -> IResult<&str, Expr> {
@@ -57,18 +310,37 @@ pub fn parse_single_quoted_string(input: &str) -> IResult<&str, Expr> {
 pub fn parse_identifier(input: &str) -> IResult<&str, &str> {
     recognize(pair(
         alt((alpha1, tag("_"), tag("$"))),
-        opt(take_while(|c: char| {
-            c.is_alphanumeric() || c == '_' || c == '-'
-        })),
+        opt(take_while(is_identifier_char)),
     ))(input)
 }
 
-pub fn parse_variable_path(input: &str) -> IResult<&str, String> {
-    map(separated_list1(tag("."), parse_identifier), |parts| {
-        parts.join(".")
+/// A field name written as a double-quoted string, for names that are not
+/// identifiers, such as `input."first-name"`. It may not contain a `.`, which
+/// separates the parts of a path.
+fn parse_quoted_field(input: &str) -> IResult<&str, String> {
+    map_opt(parse_double_quoted_string, |name| match name {
+        Expr::String(name) if !name.contains('.') => Some(name),
+        _ => None,
     })(input)
 }
 
+/// Parses a dotted path such as `user.address.city`. Parts after the first
+/// may be quoted, as `-` in a bare name would be read as subtraction.
+pub fn parse_variable_path(input: &str) -> IResult<&str, String> {
+    let (input, first) = parse_identifier(input)?;
+    let (input, rest) = many0(preceded(
+        tag("."),
+        alt((map(parse_identifier, str::to_string), parse_quoted_field)),
+    ))(input)?;
+    Ok((
+        input,
+        std::iter::once(first.to_string())
+            .chain(rest)
+            .collect::<Vec<_>>()
+            .join("."),
+    ))
+}
+
 pub fn parse_predicate(input: &str) -> IResult<&str, Expr> {
     delimited(
         preceded(multispace0, tag("[")),
@@ -77,21 +349,149 @@ pub fn parse_predicate(input: &str) -> IResult<&str, Expr> {
     )(input)
 }
 
+/// Parses a zero-based `[N]` subscript; negative values count from the end.
+pub fn parse_index(input: &str) -> IResult<&str, Expr> {
+    delimited(
+        pair(preceded(multispace0, tag("[")), multispace0),
+        map_res(recognize(pair(opt(char('-')), digit1)), |s: &str| {
+            s.parse().map(Expr::Integer)
+        }),
+        pair(multispace0, tag("]")),
+    )(input)
+}
+
 pub fn parse_variable_with_predicate(input: &str) -> IResult<&str, Expr> {
     let (input, var_path) = parse_variable_path(input)?;
-    let (input, predicate) = opt(parse_predicate)(input)?;
+    let (input, index) = opt(parse_index)(input)?;
+    let (input, expr) = match index {
+        Some(index) => (
+            input,
+            Expr::Index(Box::new(Expr::Variable(var_path)), Box::new(index)),
+        ),
+        None => match opt(parse_predicate)(input)? {
+            (input, Some(pred)) => (input, Expr::Predicate(var_path, Box::new(pred))),
+            (input, None) => (input, Expr::Variable(var_path)),
+        },
+    };
+    let (input, indexes) = many0(parse_index)(input)?;
 
-    match predicate {
-        Some(pred) => Ok((input, Expr::Predicate(var_path, Box::new(pred)))),
-        None => Ok((input, Expr::Variable(var_path))),
-    }
+    Ok((
+        input,
+        indexes.into_iter().fold(expr, |acc, index| {
+            Expr::Index(Box::new(acc), Box::new(index))
+        }),
+    ))
+}
+
+/// Parses a function call such as `substr($1, 0, 3)`, optionally followed by
+/// `[N]` subscripts. The name must be followed directly by the opening
+/// parenthesis.
+pub fn parse_call(input: &str) -> IResult<&str, Expr> {
+    let (input, (name, args)) = pair(
+        parse_identifier,
+        delimited(
+            tag("("),
+            separated_list0(tag(","), delimited(multispace0, parse_expr, multispace0)),
+            pair(multispace0, tag(")")),
+        ),
+    )(input)?;
+    let (input, indexes) = many0(parse_index)(input)?;
+
+    Ok((
+        input,
+        indexes
+            .into_iter()
+            .fold(Expr::Call(name.to_string(), args), |acc, index| {
+                Expr::Index(Box::new(acc), Box::new(index))
+            }),
+    ))
+}
+
+/// Parses the call form of a cast, `type(expr)` or `type(expr, format)`.
+pub fn parse_cast(input: &str) -> IResult<&str, Expr> {
+    map(
+        pair(
+            parse_type_name,
+            delimited(
+                pair(tag("("), multispace0),
+                pair(
+                    parse_expr,
+                    opt(preceded(
+                        tuple((multispace0, tag(","), multispace0)),
+                        parse_expr,
+                    )),
+                ),
+                pair(multispace0, tag(")")),
+            ),
+        ),
+        |(type_name, (expr, format))| Expr::Cast(Box::new(expr), type_name, format.map(Box::new)),
+    )(input)
+}
+
+/// Parses `case when cond then value ... [else value] end` into nested
+/// conditionals. Without an `else`, a chain no branch matches is null.
+pub fn parse_case(input: &str) -> IResult<&str, Expr> {
+    let branch = pair(
+        preceded(
+            tuple((multispace0, keyword("when"), multispace0)),
+            parse_expr,
+        ),
+        preceded(
+            tuple((multispace0, keyword("then"), multispace0)),
+            parse_expr,
+        ),
+    );
+    let (input, (branches, otherwise)) = delimited(
+        keyword("case"),
+        pair(
+            many1(branch),
+            opt(preceded(
+                tuple((multispace0, keyword("else"), multispace0)),
+                parse_expr,
+            )),
+        ),
+        pair(multispace0, keyword("end")),
+    )(input)?;
+
+    let otherwise = otherwise.unwrap_or(Expr::Null(TypeName::Null));
+    Ok((
+        input,
+        branches
+            .into_iter()
+            .rev()
+            .fold(otherwise, |acc, (cond, value)| {
+                Expr::Conditional(Box::new(cond), Box::new(value), Box::new(acc))
+            }),
+    ))
+}
+
+fn parse_items<'a>(open: char, close: char) -> impl FnMut(&'a str) -> IResult<&'a str, Vec<Expr>> {
+    delimited(
+        pair(char(open), multispace0),
+        separated_list0(tuple((multispace0, char(','), multispace0)), parse_expr),
+        pair(multispace0, char(close)),
+    )
+}
+
+/// Parses a `[a, b, ...]` list literal.
+pub fn parse_list(input: &str) -> IResult<&str, Expr> {
+    map(parse_items('[', ']'), Expr::List)(input)
 }
 
 pub fn parse_atom(input: &str) -> IResult<&str, Expr> {
     alt((
+        parse_timestamp,
         parse_number,
+        parse_cast,
+        parse_boolean,
+        parse_null,
+        parse_symbol,
+        parse_case,
+        parse_list,
+        parse_regex,
         parse_double_quoted_string,
         parse_single_quoted_string,
+        parse_call,
         parse_variable_with_predicate,
         delimited(
             preceded(multispace0, tag("(")),
@@ -101,21 +501,200 @@ pub fn parse_atom(input: &str) -> IResult<&str, Expr> {
     ))(input)
 }
 
+pub fn parse_unary(input: &str) -> IResult<&str, Expr> {
+    alt((
+        parse_atom,
+        map(preceded(pair(tag("-"), multispace0), parse_unary), |expr| {
+            Expr::Negate(Box::new(expr))
+        }),
+    ))(input)
+}
+
+/// Parses `expr as type` casts, which bind tighter than any binary operator:
+/// `$2 as int * 2` is `($2 as int) * 2`.
+pub fn parse_as(input: &str) -> IResult<&str, Expr> {
+    let (input, first) = parse_unary(input)?;
+    let (input, types) = many0(preceded(
+        tuple((multispace0, keyword("as"), multispace0)),
+        parse_type_name,
+    ))(input)?;
+
+    Ok((
+        input,
+        types.into_iter().fold(first, |acc, type_name| {
+            Expr::Cast(Box::new(acc), type_name, None)
+        }),
+    ))
+}
+
+pub fn parse_multiplicative(input: &str) -> IResult<&str, Expr> {
+    let (input, first) = parse_as(input)?;
+    let (input, rest) = many0(pair(
+        preceded(multispace0, alt((tag("*"), tag("/"), tag("%")))),
+        preceded(multispace0, parse_as),
+    ))(input)?;
+
+    Ok((
+        input,
+        rest.into_iter().fold(first, |acc, (op, expr)| match op {
+            "*" => Expr::Multiply(Box::new(acc), Box::new(expr)),
+            "/" => Expr::Divide(Box::new(acc), Box::new(expr)),
+            "%" => Expr::Modulo(Box::new(acc), Box::new(expr)),
+            _ => unreachable!(),
+        }),
+    ))
+}
+
+pub fn parse_additive(input: &str) -> IResult<&str, Expr> {
+    let (input, first) = parse_multiplicative(input)?;
+    let (input, rest) = many0(pair(
+        preceded(multispace0, alt((tag("+"), tag("-")))),
+        preceded(multispace0, parse_multiplicative),
+    ))(input)?;
+
+    Ok((
+        input,
+        rest.into_iter().fold(first, |acc, (op, expr)| match op {
+            "+" => Expr::Add(Box::new(acc), Box::new(expr)),
+            "-" => Expr::Subtract(Box::new(acc), Box::new(expr)),
+            _ => unreachable!(),
+        }),
+    ))
+}
+
+/// Parses the `is [not] null` or `is [not] missing` test that may follow an
+/// operand.
+pub fn parse_is(input: &str) -> IResult<&str, (bool, &str)> {
+    preceded(
+        tuple((multispace0, keyword("is"), multispace0)),
+        pair(
+            map(opt(terminated(keyword("not"), multispace0)), |not| {
+                not.is_some()
+            }),
+            alt((keyword("null"), keyword("missing"))),
+        ),
+    )(input)
+}
+
+/// The `[not] in (...)`, `[not] between low and high` or `[not] [i]like
+/// pattern` test that may follow an operand.
+enum PostfixTest {
+    In(Expr),
+    Between(Expr, Expr),
+    Like(Expr),
+    ILike(Expr),
+}
+
+/// Parses a postfix test. `in` takes a parenthesised list of values or any
+/// expression whose value is a list.
+fn parse_postfix_test(input: &str) -> IResult<&str, (bool, PostfixTest)> {
+    preceded(
+        multispace0,
+        pair(
+            map(opt(terminated(keyword("not"), multispace0)), |not| {
+                not.is_some()
+            }),
+            alt((
+                map(
+                    preceded(
+                        pair(keyword("in"), multispace0),
+                        alt((map(parse_items('(', ')'), Expr::List), parse_additive)),
+                    ),
+                    PostfixTest::In,
+                ),
+                map(
+                    pair(
+                        preceded(pair(keyword("between"), multispace0), parse_additive),
+                        preceded(
+                            tuple((multispace0, keyword("and"), multispace0)),
+                            parse_additive,
+                        ),
+                    ),
+                    |(low, high)| PostfixTest::Between(low, high),
+                ),
+                map(
+                    preceded(pair(keyword("like"), multispace0), parse_additive),
+                    PostfixTest::Like,
+                ),
+                map(
+                    preceded(pair(keyword("ilike"), multispace0), parse_additive),
+                    PostfixTest::ILike,
+                ),
+            )),
+        ),
+    )(input)
+}
+
+/// Parses a comparison, optionally followed by `collate name`.
 pub fn parse_comparison(input: &str) -> IResult<&str, Expr> {
-    let (input, left) = parse_atom(input)?;
+    let (input, expr) = parse_test(input)?;
+    let (input, collation) = opt(preceded(
+        tuple((multispace0, keyword("collate"), multispace0)),
+        recognize(separated_list1(
+            char('+'),
+            take_while1(|c: char| c.is_alphanumeric() || c == '_'),
+        )),
+    ))(input)?;
+
+    Ok((
+        input,
+        match collation {
+            Some(collation) => Expr::Collate(Box::new(expr), collation.to_string()),
+            None => expr,
+        },
+    ))
+}
+
+fn parse_test(input: &str) -> IResult<&str, Expr> {
+    let (input, left) = parse_additive(input)?;
+    if let (input, Some((negated, test))) = opt(parse_postfix_test)(input)? {
+        let expr = match test {
+            PostfixTest::In(list) => Expr::In(Box::new(left), Box::new(list)),
+            PostfixTest::Between(low, high) => {
+                Expr::Between(Box::new(left), Box::new(low), Box::new(high))
+            }
+            PostfixTest::Like(pattern) => Expr::Like(Box::new(left), Box::new(pattern)),
+            PostfixTest::ILike(pattern) => Expr::ILike(Box::new(left), Box::new(pattern)),
+        };
+        return Ok((
+            input,
+            if negated {
+                Expr::Not(Box::new(expr))
+            } else {
+                expr
+            },
+        ));
+    }
+    if let (input, Some((negated, test))) = opt(parse_is)(input)? {
+        let expr = match test {
+            "null" => Expr::IsNull(Box::new(left)),
+            "missing" => Expr::IsMissing(Box::new(left)),
+            _ => unreachable!(),
+        };
+        return Ok((
+            input,
+            if negated {
+                Expr::Not(Box::new(expr))
+            } else {
+                expr
+            },
+        ));
+    }
     let (input, rest) = opt(pair(
         preceded(
             multispace0,
             alt((
                 tag("=="),
                 tag("!="),
+                tag("!~"),
                 tag("<="),
                 tag(">="),
                 tag("<"),
                 tag(">"),
+                tag("~"),
             )),
         ),
-        preceded(multispace0, parse_atom),
+        preceded(multispace0, parse_additive),
     ))(input)?;
 
     match rest {
@@ -127,6 +706,8 @@ pub fn parse_comparison(input: &str) -> IResult<&str, Expr> {
                 ">=" => Expr::GreaterThanOrEqual(Box::new(left), Box::new(right)),
                 "<" => Expr::LessThan(Box::new(left), Box::new(right)),
                 ">" => Expr::GreaterThan(Box::new(left), Box::new(right)),
+                "~" => Expr::Match(Box::new(left), Box::new(right)),
+                "!~" => Expr::NotMatch(Box::new(left), Box::new(right)),
                 _ => unreachable!(),
             };
             Ok((input, expr))
@@ -135,11 +716,22 @@ pub fn parse_comparison(input: &str) -> IResult<&str, Expr> {
     }
 }
 
+/// Parses `!` prefixes. `!` binds looser than comparisons but tighter than
+/// `&&`, so `!a == b` is `!(a == b)` and `!a && b` is `(!a) && b`.
+pub fn parse_not(input: &str) -> IResult<&str, Expr> {
+    alt((
+        map(preceded(pair(tag("!"), multispace0), parse_not), |expr| {
+            Expr::Not(Box::new(expr))
+        }),
+        parse_comparison,
+    ))(input)
+}
+
 pub fn parse_and(input: &str) -> IResult<&str, Expr> {
-    let (input, first) = parse_comparison(input)?;
+    let (input, first) = parse_not(input)?;
     let (input, rest) = many0(preceded(
         multispace0,
-        pair(tag("&&"), preceded(multispace0, parse_comparison)),
+        pair(tag("&&"), preceded(multispace0, parse_not)),
     ))(input)?;
 
     Ok((
@@ -165,8 +757,49 @@ pub fn parse_or(input: &str) -> IResult<&str, Expr> {
     ))
 }
 
+pub fn parse_coalesce(input: &str) -> IResult<&str, Expr> {
+    let (input, first) = parse_or(input)?;
+    let (input, rest) = many0(preceded(
+        multispace0,
+        pair(tag("??"), preceded(multispace0, parse_or)),
+    ))(input)?;
+
+    Ok((
+        input,
+        rest.into_iter().fold(first, |acc, (_, expr)| {
+            Expr::Coalesce(Box::new(acc), Box::new(expr))
+        }),
+    ))
+}
+
+/// Parses `cond ? a : b`, the loosest-binding form; it nests to the right,
+/// so `a ? b : c ? d : e` is `a ? b : (c ? d : e)`.
+pub fn parse_conditional(input: &str) -> IResult<&str, Expr> {
+    let (input, cond) = parse_coalesce(input)?;
+    let (input, branches) = opt(pair(
+        preceded(
+            pair(multispace0, char('?')),
+            preceded(multispace0, parse_expr),
+        ),
+        preceded(
+            pair(multispace0, char(':')),
+            preceded(multispace0, parse_expr),
+        ),
+    ))(input)?;
+
+    Ok((
+        input,
+        match branches {
+            Some((then, otherwise)) => {
+                Expr::Conditional(Box::new(cond), Box::new(then), Box::new(otherwise))
+            }
+            None => cond,
+        },
+    ))
+}
+
 pub fn parse_expr(input: &str) -> IResult<&str, Expr> {
-    parse_or(input)
+    parse_conditional(input)
 }
 
 #[cfg(test)]
@@ -185,6 +818,482 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_index() {
+        let (remaining, expr) = parse_expr("input[0] == input[ -1 ]").unwrap();
+        assert_eq!(remaining, "");
+        assert_eq!(
+            expr,
+            Expr::Equal(
+                Box::new(Expr::Index(
+                    Box::new(Expr::Variable("input".to_string())),
+                    Box::new(Expr::Integer(0)),
+                )),
+                Box::new(Expr::Index(
+                    Box::new(Expr::Variable("input".to_string())),
+                    Box::new(Expr::Integer(-1)),
+                )),
+            )
+        );
+
+        let (_, expr) = parse_expr("abc[a == 5][-1][0]").unwrap();
+        assert_eq!(
+            expr,
+            Expr::Index(
+                Box::new(Expr::Index(
+                    Box::new(Expr::Predicate(
+                        "abc".to_string(),
+                        Box::new(Expr::Equal(
+                            Box::new(Expr::Variable("a".to_string())),
+                            Box::new(Expr::Integer(5)),
+                        )),
+                    )),
+                    Box::new(Expr::Integer(-1)),
+                )),
+                Box::new(Expr::Integer(0)),
+            )
+        );
+    }
+
+    #[test]
+    fn test_arithmetic_precedence() {
+        let (remaining, expr) = parse_expr("$3 * $4 > 1000 - -$5 % 7").unwrap();
+        assert_eq!(remaining, "");
+        let var = |name: &str| Box::new(Expr::Variable(name.to_string()));
+        assert_eq!(
+            expr,
+            Expr::GreaterThan(
+                Box::new(Expr::Multiply(var("$3"), var("$4"))),
+                Box::new(Expr::Subtract(
+                    Box::new(Expr::Integer(1000)),
+                    Box::new(Expr::Modulo(
+                        Box::new(Expr::Negate(var("$5"))),
+                        Box::new(Expr::Integer(7)),
+                    )),
+                )),
+            )
+        );
+
+        let (_, expr) = parse_expr("(1 + 2) * 3 - 4 - 5").unwrap();
+        assert_eq!(
+            expr,
+            Expr::Subtract(
+                Box::new(Expr::Subtract(
+                    Box::new(Expr::Multiply(
+                        Box::new(Expr::Add(
+                            Box::new(Expr::Integer(1)),
+                            Box::new(Expr::Integer(2)),
+                        )),
+                        Box::new(Expr::Integer(3)),
+                    )),
+                    Box::new(Expr::Integer(4)),
+                )),
+                Box::new(Expr::Integer(5)),
+            )
+        );
+
+        // `-` ends a name, so subtraction needs no spaces.
+        let var = |name: &str| Box::new(Expr::Variable(name.to_string()));
+        assert_eq!(
+            parse_expr("$3-$4").unwrap(),
+            ("", Expr::Subtract(var("$3"), var("$4")))
+        );
+        assert_eq!(
+            parse_expr("$3-1").unwrap(),
+            ("", Expr::Subtract(var("$3"), Box::new(Expr::Integer(1))))
+        );
+        assert_eq!(
+            parse_expr("x-1").unwrap(),
+            ("", Expr::Subtract(var("x"), Box::new(Expr::Integer(1))))
+        );
+        // Hyphenated field names are quoted instead.
+        assert_eq!(
+            parse_expr("input.\"first-name\"-1").unwrap(),
+            (
+                "",
+                Expr::Subtract(var("input.first-name"), Box::new(Expr::Integer(1)))
+            )
+        );
+        assert_eq!(parse_expr("input.\"a.b\"").unwrap().0, ".\"a.b\"");
+    }
+
+    #[test]
+    fn test_number() {
+        let number = |input| parse_number(input).unwrap().1;
+        assert_eq!(number("42"), Expr::Integer(42));
+        assert_eq!(number("-3"), Expr::Integer(-3));
+        assert_eq!(number("9.99"), Expr::Decimal(Decimal::new(999, 2)));
+        assert_eq!(number("-0.50"), Expr::Decimal(Decimal::new(-50, 2)));
+        assert_eq!(number("1.25d1"), Expr::Decimal(Decimal::new(125, 1)));
+        assert_eq!(number("1.5e3"), Expr::Float(1500.0));
+        assert_eq!(number("2E-2"), Expr::Float(0.02));
+        assert_eq!(number("-9223372036854775808"), Expr::Integer(i64::MIN));
+
+        let (_, expr) = parse_expr("x > -3 && price <= 9.99").unwrap();
+        assert_eq!(
+            expr,
+            Expr::And(
+                Box::new(Expr::GreaterThan(
+                    Box::new(Expr::Variable("x".to_string())),
+                    Box::new(Expr::Integer(-3)),
+                )),
+                Box::new(Expr::LessThanOrEqual(
+                    Box::new(Expr::Variable("price".to_string())),
+                    Box::new(Expr::Decimal(Decimal::new(999, 2))),
+                )),
+            )
+        );
+        let (_, expr) = parse_expr("10 -3").unwrap();
+        assert_eq!(
+            expr,
+            Expr::Subtract(Box::new(Expr::Integer(10)), Box::new(Expr::Integer(3)))
+        );
+    }
+
+    #[test]
+    fn test_literals() {
+        let atom = |input| parse_atom(input).unwrap();
+        assert_eq!(atom("true"), ("", Expr::Boolean(true)));
+        assert_eq!(atom("false"), ("", Expr::Boolean(false)));
+        assert_eq!(
+            atom("true_ish"),
+            ("", Expr::Variable("true_ish".to_string()))
+        );
+        assert_eq!(atom("null"), ("", Expr::Null(TypeName::Null)));
+        assert_eq!(atom("null.int"), ("", Expr::Null(TypeName::Int)));
+        assert_eq!(atom("null.sexp"), ("", Expr::Null(TypeName::SExp)));
+        assert_eq!(atom("`active`"), ("", Expr::Symbol("active".to_string())));
+        for timestamp in [
+            "2024T",
+            "2024-01T",
+            "2024-01-01",
+            "2024-01-01T",
+            "2024-01-01T12:30Z",
+            "2024-01-01T12:30:59.125-05:00",
+        ] {
+            assert_eq!(
+                atom(timestamp),
+                ("", Expr::Timestamp(timestamp.to_string()))
+            );
+        }
+
+        let (_, expr) = parse_expr("x < 2024-03-10T09:00+00:00").unwrap();
+        assert_eq!(
+            expr,
+            Expr::LessThan(
+                Box::new(Expr::Variable("x".to_string())),
+                Box::new(Expr::Timestamp("2024-03-10T09:00+00:00".to_string())),
+            )
+        );
+        let (_, expr) = parse_expr("2024-1 - 1").unwrap();
+        assert_eq!(
+            expr,
+            Expr::Subtract(
+                Box::new(Expr::Subtract(
+                    Box::new(Expr::Integer(2024)),
+                    Box::new(Expr::Integer(1)),
+                )),
+                Box::new(Expr::Integer(1)),
+            )
+        );
+    }
+
+    #[test]
+    fn test_not() {
+        let var = |name: &str| Box::new(Expr::Variable(name.to_string()));
+        let (remaining, expr) = parse_expr("!$1 == 5 && !!b || !(c && d)").unwrap();
+        assert_eq!(remaining, "");
+        assert_eq!(
+            expr,
+            Expr::Or(
+                Box::new(Expr::And(
+                    Box::new(Expr::Not(Box::new(Expr::Equal(
+                        var("$1"),
+                        Box::new(Expr::Integer(5)),
+                    )))),
+                    Box::new(Expr::Not(Box::new(Expr::Not(var("b"))))),
+                )),
+                Box::new(Expr::Not(Box::new(Expr::And(var("c"), var("d"))))),
+            )
+        );
+    }
+
+    #[test]
+    fn test_regex() {
+        let atom = |input| parse_atom(input).unwrap();
+        assert_eq!(atom("/^ro+t$/"), ("", Expr::Regex("^ro+t$".to_string())));
+        assert_eq!(
+            atom(r"/\/bin\/(\w+)/"),
+            ("", Expr::Regex(r"/bin/(\w+)".to_string()))
+        );
+        assert_eq!(atom("/error/i"), ("", Expr::Regex("(?i)error".to_string())));
+
+        let var = |name: &str| Box::new(Expr::Variable(name.to_string()));
+        let (remaining, expr) = parse_expr("$3 ~ /^a/ && $4 !~ $5 / 2").unwrap();
+        assert_eq!(remaining, "");
+        assert_eq!(
+            expr,
+            Expr::And(
+                Box::new(Expr::Match(
+                    var("$3"),
+                    Box::new(Expr::Regex("^a".to_string())),
+                )),
+                Box::new(Expr::NotMatch(
+                    var("$4"),
+                    Box::new(Expr::Divide(var("$5"), Box::new(Expr::Integer(2)))),
+                )),
+            )
+        );
+    }
+
+    #[test]
+    fn test_call() {
+        let var = |name: &str| Expr::Variable(name.to_string());
+        let (remaining, expr) = parse_expr("f() + g( $1 , h(2) ) > 3").unwrap();
+        assert_eq!(remaining, "");
+        assert_eq!(
+            expr,
+            Expr::GreaterThan(
+                Box::new(Expr::Add(
+                    Box::new(Expr::Call("f".to_string(), vec![])),
+                    Box::new(Expr::Call(
+                        "g".to_string(),
+                        vec![
+                            var("$1"),
+                            Expr::Call("h".to_string(), vec![Expr::Integer(2)])
+                        ],
+                    )),
+                )),
+                Box::new(Expr::Integer(3)),
+            )
+        );
+        assert_eq!(
+            parse_expr("f(1)[0]").unwrap().1,
+            Expr::Index(
+                Box::new(Expr::Call("f".to_string(), vec![Expr::Integer(1)])),
+                Box::new(Expr::Integer(0)),
+            )
+        );
+        assert_eq!(parse_expr("f (1)").unwrap(), (" (1)", var("f")));
+        assert_eq!(parse_expr("f(1,)").unwrap().0, "(1,)");
+    }
+
+    #[test]
+    fn test_cast() {
+        let var = |name: &str| Box::new(Expr::Variable(name.to_string()));
+        let (remaining, expr) = parse_expr("int($2) > $3 as decimal * 2").unwrap();
+        assert_eq!(remaining, "");
+        assert_eq!(
+            expr,
+            Expr::GreaterThan(
+                Box::new(Expr::Cast(var("$2"), TypeName::Int, None)),
+                Box::new(Expr::Multiply(
+                    Box::new(Expr::Cast(var("$3"), TypeName::Decimal, None)),
+                    Box::new(Expr::Integer(2)),
+                )),
+            )
+        );
+        assert_eq!(
+            parse_expr("timestamp( $4, \"%d/%m/%Y\" )").unwrap().1,
+            Expr::Cast(
+                var("$4"),
+                TypeName::Timestamp,
+                Some(Box::new(Expr::String("%d/%m/%Y".to_string()))),
+            )
+        );
+        assert_eq!(
+            parse_expr("-x as float as string").unwrap().1,
+            Expr::Cast(
+                Box::new(Expr::Cast(
+                    Box::new(Expr::Negate(var("x"))),
+                    TypeName::Float,
+                    None
+                )),
+                TypeName::String,
+                None,
+            )
+        );
+        assert_eq!(parse_expr("int").unwrap().1, *var("int"));
+        assert_eq!(parse_expr("x asint").unwrap().0, " asint");
+    }
+
+    #[test]
+    fn test_is() {
+        let var = |name: &str| Box::new(Expr::Variable(name.to_string()));
+        assert_eq!(
+            parse_expr("$7 is null || $7 + 1 is not missing").unwrap().1,
+            Expr::Or(
+                Box::new(Expr::IsNull(var("$7"))),
+                Box::new(Expr::Not(Box::new(Expr::IsMissing(Box::new(Expr::Add(
+                    var("$7"),
+                    Box::new(Expr::Integer(1))
+                )))))),
+            )
+        );
+        assert_eq!(
+            parse_expr("!x is null").unwrap().1,
+            Expr::Not(Box::new(Expr::IsNull(var("x"))))
+        );
+        assert_eq!(parse_expr("x is nullish").unwrap().0, " is nullish");
+        assert_eq!(parse_expr("x isnull").unwrap().0, " isnull");
+    }
+
+    #[test]
+    fn test_conditional() {
+        let var = |name: &str| Box::new(Expr::Variable(name.to_string()));
+        let int = |v| Box::new(Expr::Integer(v));
+        assert_eq!(
+            parse_expr("$1 ?? $2 ?? 0").unwrap().1,
+            Expr::Coalesce(Box::new(Expr::Coalesce(var("$1"), var("$2"))), int(0))
+        );
+        assert_eq!(
+            parse_expr("a || b ? 1 : c ? 2 : 3").unwrap().1,
+            Expr::Conditional(
+                Box::new(Expr::Or(var("a"), var("b"))),
+                int(1),
+                Box::new(Expr::Conditional(var("c"), int(2), int(3))),
+            )
+        );
+        assert_eq!(
+            parse_expr("case when a then 1 when b then 2 else 3 end + 1")
+                .unwrap()
+                .1,
+            Expr::Add(
+                Box::new(Expr::Conditional(
+                    var("a"),
+                    int(1),
+                    Box::new(Expr::Conditional(var("b"), int(2), int(3))),
+                )),
+                int(1),
+            )
+        );
+        assert_eq!(
+            parse_expr("case when a > 1 then a end").unwrap().1,
+            Expr::Conditional(
+                Box::new(Expr::GreaterThan(var("a"), int(1))),
+                var("a"),
+                Box::new(Expr::Null(TypeName::Null)),
+            )
+        );
+        assert_eq!(parse_expr("case").unwrap().1, *var("case"));
+        assert_eq!(parse_expr("a ? 1").unwrap().0, " ? 1");
+    }
+
+    #[test]
+    fn test_membership() {
+        let var = |name: &str| Box::new(Expr::Variable(name.to_string()));
+        let int = |v| Expr::Integer(v);
+        assert_eq!(
+            parse_expr("[1, \"a\", [] ]").unwrap().1,
+            Expr::List(vec![
+                int(1),
+                Expr::String("a".to_string()),
+                Expr::List(vec![])
+            ])
+        );
+        assert_eq!(
+            parse_expr("$1 in (1, 2) && $2 not in [3]").unwrap().1,
+            Expr::And(
+                Box::new(Expr::In(
+                    var("$1"),
+                    Box::new(Expr::List(vec![int(1), int(2)]))
+                )),
+                Box::new(Expr::Not(Box::new(Expr::In(
+                    var("$2"),
+                    Box::new(Expr::List(vec![int(3)]))
+                )))),
+            )
+        );
+        assert_eq!(
+            parse_expr("`a` in tags").unwrap().1,
+            Expr::In(Box::new(Expr::Symbol("a".to_string())), var("tags"))
+        );
+        assert_eq!(
+            parse_expr("x + 1 not between 0 and y * 2").unwrap().1,
+            Expr::Not(Box::new(Expr::Between(
+                Box::new(Expr::Add(var("x"), Box::new(int(1)))),
+                Box::new(int(0)),
+                Box::new(Expr::Multiply(var("y"), Box::new(int(2)))),
+            )))
+        );
+        assert_eq!(parse_expr("x between 1").unwrap().0, " between 1");
+        assert_eq!(parse_expr("x inside").unwrap().0, " inside");
+    }
+
+    #[test]
+    fn test_like() {
+        let var = |name: &str| Box::new(Expr::Variable(name.to_string()));
+        let string = |text: &str| Box::new(Expr::String(text.to_string()));
+        assert_eq!(
+            parse_expr("$1 like \"a%\" || $2 not ilike 'B_'").unwrap().1,
+            Expr::Or(
+                Box::new(Expr::Like(var("$1"), string("a%"))),
+                Box::new(Expr::Not(Box::new(Expr::ILike(var("$2"), string("B_"))))),
+            )
+        );
+        assert_eq!(
+            parse_expr("name like prefix + \"%\"").unwrap().1,
+            Expr::Like(var("name"), Box::new(Expr::Add(var("prefix"), string("%"))))
+        );
+        assert_eq!(parse_expr("x likes y").unwrap().0, " likes y");
+    }
+
+    #[test]
+    fn test_collate() {
+        let var = |name: &str| Box::new(Expr::Variable(name.to_string()));
+        assert_eq!(
+            parse_expr("a < b collate nfkc+nocase && c").unwrap().1,
+            Expr::And(
+                Box::new(Expr::Collate(
+                    Box::new(Expr::LessThan(var("a"), var("b"))),
+                    "nfkc+nocase".to_string()
+                )),
+                var("c"),
+            )
+        );
+        assert_eq!(
+            parse_expr("(a == b || a in (c)) collate nocase").unwrap().1,
+            Expr::Collate(
+                Box::new(Expr::Or(
+                    Box::new(Expr::Equal(var("a"), var("b"))),
+                    Box::new(Expr::In(var("a"), Box::new(Expr::List(vec![*var("c")])))),
+                )),
+                "nocase".to_string()
+            )
+        );
+        assert_eq!(
+            parse_expr("f(a == b collate nocase, c)").unwrap().1,
+            Expr::Call(
+                "f".to_string(),
+                vec![
+                    Expr::Collate(
+                        Box::new(Expr::Equal(var("a"), var("b"))),
+                        "nocase".to_string()
+                    ),
+                    *var("c"),
+                ]
+            )
+        );
+        assert_eq!(parse_expr("a collate").unwrap().0, " collate");
+    }
+
+    #[test]
+    fn test_children() {
+        let (_, expr) = parse_expr("f($1, 2) ? int(x) : y between 1 and 2").unwrap();
+        let children = expr.children();
+        assert_eq!(children.len(), 3);
+        assert_eq!(
+            children[0].children(),
+            vec![&Expr::Variable("$1".to_string()), &Expr::Integer(2)]
+        );
+        assert_eq!(
+            children[1].children(),
+            vec![&Expr::Variable("x".to_string())]
+        );
+        assert_eq!(children[2].children().len(), 3);
+        assert!(children[2].children()[1].children().is_empty());
+    }
+
     #[test]
     fn test_predicate() {
         let input_predicate = "[$1 == 5 && (b < 10 || c >= 20)]";
@@ -0,0 +1,180 @@
+use nom::{
+    branch::alt,
+    bytes::complete::{tag, take_while1},
+    character::complete::{line_ending, multispace0, not_line_ending, space0},
+    combinator::{all_consuming, map, map_res, opt},
+    error::{Error, ErrorKind},
+    multi::many0,
+    sequence::{pair, preceded, terminated, tuple},
+    IResult,
+};
+
+use crate::{
+    parse_predicate,
+    template::{parse_template, Template},
+    Expr,
+};
+
+/// A single pattern–action pair. A rule without a predicate runs for every
+/// record; a rule without an action prints the matching record.
+#[derive(Debug, PartialEq)]
+pub struct Rule {
+    pub predicate: Option<Expr>,
+    pub action: Option<Template>,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct Program {
+    pub rules: Vec<Rule>,
+}
+
+pub fn parse_inline_action(input: &str) -> IResult<&str, String> {
+    map(
+        preceded(
+            preceded(space0, tag(":")),
+            preceded(space0, not_line_ending),
+        ),
+        |action: &str| action.trim_end().to_string(),
+    )(input)
+}
+
+/// Parses a `<<WORD` action whose body runs, verbatim, up to a line holding
+/// only `WORD`. The line ending before the terminator is not part of the body.
+pub fn parse_heredoc_action(input: &str) -> IResult<&str, String> {
+    let (input, terminator) = preceded(
+        pair(space0, tag("<<")),
+        take_while1(|c: char| c.is_alphanumeric() || c == '_'),
+    )(input)?;
+    let (body, _) = tuple((opt(tag(":")), space0, line_ending))(input)?;
+
+    let mut offset = 0;
+    loop {
+        let line_end = body[offset..].find('\n').map(|i| offset + i);
+        let line = &body[offset..line_end.unwrap_or(body.len())];
+        if line.trim() == terminator {
+            let text = &body[..offset];
+            let text = text
+                .strip_suffix("\r\n")
+                .or_else(|| text.strip_suffix('\n'))
+                .unwrap_or(text);
+            let rest = &body[line_end.unwrap_or(body.len())..];
+            return Ok((rest, text.to_string()));
+        }
+        match line_end {
+            Some(line_end) => offset = line_end + 1,
+            None => return Err(nom::Err::Failure(Error::new(input, ErrorKind::TakeUntil))),
+        }
+    }
+}
+
+pub fn parse_rule(input: &str) -> IResult<&str, Rule> {
+    let (input, predicate) = opt(parse_predicate)(input)?;
+    let (input, action) = opt(map_res(
+        alt((parse_heredoc_action, parse_inline_action)),
+        |text| {
+            parse_template(&text)
+                .map(|(_, template)| template)
+                .map_err(|e| e.to_string())
+        },
+    ))(input)?;
+
+    if predicate.is_none() && action.is_none() {
+        return Err(nom::Err::Error(Error::new(input, ErrorKind::Verify)));
+    }
+    Ok((input, Rule { predicate, action }))
+}
+
+pub fn parse_program(input: &str) -> IResult<&str, Program> {
+    map(
+        all_consuming(terminated(
+            many0(preceded(multispace0, parse_rule)),
+            multispace0,
+        )),
+        |rules| Program { rules },
+    )(input)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::template::TemplatePart;
+
+    fn literal(text: &str) -> Option<Template> {
+        Some(Template {
+            parts: vec![TemplatePart::Literal(text.to_string())],
+        })
+    }
+
+    #[test]
+    fn test_program() {
+        let input = r#"
+            [$1 == "root"]: {{ $1 }} - {{ $2 }}
+            : every record
+            [$2 > 10]
+        "#;
+        let (_, program) = parse_program(input).unwrap();
+        assert_eq!(program.rules.len(), 3);
+        assert_eq!(
+            program.rules[0].predicate,
+            Some(Expr::Equal(
+                Box::new(Expr::Variable("$1".to_string())),
+                Box::new(Expr::String("root".to_string())),
+            ))
+        );
+        assert_eq!(
+            program.rules[0].action,
+            Some(Template {
+                parts: vec![
+                    TemplatePart::Expr(Expr::Variable("$1".to_string())),
+                    TemplatePart::Literal(" - ".to_string()),
+                    TemplatePart::Expr(Expr::Variable("$2".to_string())),
+                ]
+            })
+        );
+        assert_eq!(program.rules[1].predicate, None);
+        assert_eq!(program.rules[1].action, literal("every record"));
+        assert_eq!(program.rules[2].action, None);
+    }
+
+    #[test]
+    fn test_heredoc_action() {
+        let input = "<<EOF:\n  name: {{ $1 }}\n\n  shell: {{ $7 }}\n  EOF\n[$2]: next\n";
+        let (remaining, action) = parse_heredoc_action(input).unwrap();
+        assert_eq!(action, "  name: {{ $1 }}\n\n  shell: {{ $7 }}");
+        assert_eq!(remaining, "\n[$2]: next\n");
+
+        let input =
+            "[$1 == \"root\"] <<EOF:\n  name: {{ $1 }}\n\n  shell: {{ $7 }}\n  EOF\n[$2]: next\n";
+        let (_, program) = parse_program(input).unwrap();
+        assert_eq!(program.rules.len(), 2);
+        assert_eq!(
+            program.rules[0].action,
+            Some(Template {
+                parts: vec![
+                    TemplatePart::Literal("  name: ".to_string()),
+                    TemplatePart::Expr(Expr::Variable("$1".to_string())),
+                    TemplatePart::Literal("\n\n  shell: ".to_string()),
+                    TemplatePart::Expr(Expr::Variable("$7".to_string())),
+                ]
+            })
+        );
+        assert_eq!(program.rules[1].action, literal("next"));
+
+        let (_, program) = parse_program("<<SQL\nSELECT 1;\nSQL").unwrap();
+        assert_eq!(program.rules[0].predicate, None);
+        assert_eq!(program.rules[0].action, literal("SELECT 1;"));
+
+        let (_, program) = parse_program("[$1 == 5] <<SQL\nSELECT 1;\nSQL\n: next").unwrap();
+        assert_eq!(program.rules.len(), 2);
+        assert_eq!(program.rules[0].action, literal("SELECT 1;"));
+        assert_eq!(program.rules[1].action, literal("next"));
+
+        assert!(parse_program("[$1] <<EOF\nnever closed\n").is_err());
+    }
+
+    #[test]
+    fn test_program_trailing_garbage() {
+        assert!(parse_program("[$1 == 5]: ok\n$2").is_err());
+        assert!(parse_program("[$1 == 5]: {{ $1 ").is_err());
+    }
+}
@@ -0,0 +1,73 @@
+use nom::{
+    branch::alt,
+    bytes::complete::{tag, take_until},
+    character::complete::multispace0,
+    combinator::{all_consuming, map, rest, verify},
+    multi::many0,
+    sequence::{delimited, pair},
+    IResult,
+};
+
+use crate::{parse_expr, Expr};
+
+/// A piece of action text: either copied through as-is, or an expression
+/// whose value is substituted for the `{{ ... }}` hole it was written in.
+#[derive(Debug, PartialEq)]
+pub enum TemplatePart {
+    Literal(String),
+    Expr(Expr),
+}
+
+#[derive(Debug, PartialEq)]
+pub struct Template {
+    pub parts: Vec<TemplatePart>,
+}
+
+pub fn parse_template_hole(input: &str) -> IResult<&str, TemplatePart> {
+    map(
+        delimited(
+            pair(tag("{{"), multispace0),
+            parse_expr,
+            pair(multispace0, tag("}}")),
+        ),
+        TemplatePart::Expr,
+    )(input)
+}
+
+pub fn parse_template_literal(input: &str) -> IResult<&str, TemplatePart> {
+    map(
+        verify(alt((take_until("{{"), rest)), |s: &str| !s.is_empty()),
+        |s: &str| TemplatePart::Literal(s.to_string()),
+    )(input)
+}
+
+pub fn parse_template(input: &str) -> IResult<&str, Template> {
+    map(
+        all_consuming(many0(alt((parse_template_hole, parse_template_literal)))),
+        |parts| Template { parts },
+    )(input)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_template() {
+        let (_, template) = parse_template("{{ $1 }} - {{$2}}!").unwrap();
+        assert_eq!(
+            template.parts,
+            vec![
+                TemplatePart::Expr(Expr::Variable("$1".to_string())),
+                TemplatePart::Literal(" - ".to_string()),
+                TemplatePart::Expr(Expr::Variable("$2".to_string())),
+                TemplatePart::Literal("!".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_template_unclosed_hole() {
+        assert!(parse_template("name: {{ $1").is_err());
+    }
+}
@@ -1,5 +1,12 @@
 use clap::{Parser, ValueHint};
-use hawk_core::source::csv::CsvIonIterator;
+use hawk_core::{
+    source::{
+        collation::Collation, csv::CsvIonIterator, prepare_program, resolve_expr, resolve_record,
+        CoercionPolicy, Context,
+    },
+    template::{render_template, value_text},
+};
+use hawk_parser::program::parse_program;
 use ion_rs::element::Value;
 use std::{fs::File, process};
 
@@ -13,39 +20,106 @@ struct HawkArgs {
     #[arg(short = 'q')]
     query: Option<String>,
 
+    #[arg(long)]
+    header: bool,
+
+    /// Run rules for the records their predicates do not match
+    #[arg(short = 'v')]
+    invert: bool,
+
+    /// Seed for rand(), to make its results repeatable
+    #[arg(long)]
+    seed: Option<u64>,
+
+    /// How comparisons treat values of different types: strict, lenient or none
+    #[arg(long, default_value = "strict")]
+    coercion: CoercionPolicy,
+
+    /// How strings are compared: binary, or nocase, nfc, nfkc and natural
+    /// joined with +, e.g. nfkc+nocase
+    #[arg(long, default_value = "binary")]
+    collation: Collation,
+
     #[arg(name = "files", value_hint = ValueHint::FilePath)]
     files: Vec<String>,
 }
 
 fn main() {
     let args = HawkArgs::parse();
-    println!("{args:?}");
-
-    let csv_file = File::open(&args.files[0]).unwrap();
-    let reader = csv::ReaderBuilder::new()
-        .has_headers(false)
-        .from_reader(csv_file);
-    let query_expr = match hawk_parser::parse_predicate(&args.query.unwrap()) {
-        Ok((_, expr)) => expr,
+    let separator = args.separator.unwrap_or_else(|| ",".to_string());
+    let &[delimiter] = separator.as_bytes() else {
+        eprintln!("Error: field separator must be a single byte");
+        process::exit(1);
+    };
+
+    let Some(path) = args.files.first() else {
+        eprintln!("Error: no input file given");
+        process::exit(1);
+    };
+    let csv_file = match File::open(path) {
+        Ok(file) => file,
         Err(e) => {
-            println!("Error: {:?}", e);
+            eprintln!("Error: {path}: {e}");
             process::exit(1);
         }
     };
-    let ion_iterator = match CsvIonIterator::new(reader) {
+    let mut reader = csv::ReaderBuilder::new();
+    reader
+        .has_headers(args.header)
+        .delimiter(delimiter)
+        .flexible(true);
+    let program = match parse_program(args.query.as_deref().unwrap_or_default()) {
+        Ok((_, program)) => program,
+        Err(e) => {
+            eprintln!("Error: {:?}", e);
+            process::exit(1);
+        }
+    };
+    let mut ion_iterator = match CsvIonIterator::new(&reader, csv_file) {
         Ok(iter) => iter,
         _ => {
-            println!("Could not get iterator");
+            eprintln!("Could not get iterator");
             process::exit(1);
         }
     };
-    for element in ion_iterator {
+    let mut ctx = Context::new(Some(separator));
+    ctx.coercion = args.coercion;
+    ctx.collation = args.collation;
+    if let Some(seed) = args.seed {
+        ctx.seed(seed);
+    }
+    if let Err(e) = prepare_program(&ctx, &program) {
+        eprintln!("Error: {e}");
+        process::exit(1);
+    }
+    let mut record = 0;
+    while let Some(element) = ion_iterator.next() {
+        record += 1;
         if let Some(data) = element.as_struct() {
-            let torf = hawk_core::source::resolve_expr(data, &query_expr);
-            let torf = torf.unwrap();
-            if let Value::Bool(torf) = torf.as_ref() {
-                if *torf {
-                    println!(">> def: {}", data.get("1").unwrap())
+            ctx.clear_captures();
+            ctx.set_line(ion_iterator.line());
+            for rule in &program.rules {
+                if let Some(predicate) = &rule.predicate {
+                    let matched = match resolve_expr(&ctx, data, predicate) {
+                        Ok(value) => matches!(value.as_ref(), Value::Bool(true)),
+                        Err(e) => {
+                            eprintln!("Error: record {record}: {e}");
+                            process::exit(1);
+                        }
+                    };
+                    if matched == args.invert {
+                        continue;
+                    }
+                }
+                match &rule.action {
+                    Some(action) => match render_template(&ctx, data, action) {
+                        Ok(output) => println!("{output}"),
+                        Err(e) => {
+                            eprintln!("Error: record {record}: {e}");
+                            process::exit(1);
+                        }
+                    },
+                    None => println!("{}", value_text(&resolve_record(&ctx, data))),
                 }
             }
         }